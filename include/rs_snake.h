@@ -0,0 +1,65 @@
+#ifndef RS_SNAKE_H
+#define RS_SNAKE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Status codes returned by every function */
+#define RS_SNAKE_OK 0
+#define RS_SNAKE_NULL_POINTER 1
+#define RS_SNAKE_INVALID_ARGUMENT 2
+#define RS_SNAKE_GAME_OVER 3
+#define RS_SNAKE_BUFFER_TOO_SMALL 4
+#define RS_SNAKE_PANIC 5
+
+/* Game states reported by rs_snake_state and rs_snake_step */
+#define RS_SNAKE_STATE_RUNNING 0
+#define RS_SNAKE_STATE_OVER 1
+#define RS_SNAKE_STATE_WON 2
+
+/* Directions accepted by rs_snake_step */
+#define RS_SNAKE_UP 0
+#define RS_SNAKE_DOWN 1
+#define RS_SNAKE_LEFT 2
+#define RS_SNAKE_RIGHT 3
+
+typedef struct RsSnakeGame RsSnakeGame;
+
+typedef struct RsSnakeCell {
+    uint16_t x;
+    uint16_t y;
+} RsSnakeCell;
+
+/* Largest board rs_snake_new accepts, as width * height */
+#define RS_SNAKE_MAX_CELLS 1048576
+
+/* Create a game on a width x height board (at least 6 x 1, at most RS_SNAKE_MAX_CELLS
+ * cells, RS_SNAKE_INVALID_ARGUMENT otherwise). Cells range from (0, 0)
+ * to (width - 1, height - 1). The same seed always produces the same food sequence. */
+int32_t rs_snake_new(uint16_t width, uint16_t height, uint64_t seed, RsSnakeGame **out);
+
+/* Free a game created by rs_snake_new. Passing NULL is a no-op. */
+void rs_snake_free(RsSnakeGame *game);
+
+/* Advance one tick moving in `direction`. Turning back onto the snake is ignored.
+ * The resulting state is written to `state_out` unless it is NULL. */
+int32_t rs_snake_step(RsSnakeGame *game, int32_t direction, int32_t *state_out);
+
+int32_t rs_snake_state(const RsSnakeGame *game, int32_t *state_out);
+
+int32_t rs_snake_food(const RsSnakeGame *game, RsSnakeCell *food_out);
+
+/* Copy the snake, head first, into `cells`. The snake length is always written to
+ * `len_out`; RS_SNAKE_BUFFER_TOO_SMALL is returned if it exceeds `capacity`. */
+int32_t rs_snake_cells(const RsSnakeGame *game, RsSnakeCell *cells, size_t capacity,
+                       size_t *len_out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RS_SNAKE_H */
@@ -0,0 +1,200 @@
+use std::collections::{HashSet, VecDeque};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::game_instance::{Direction, GameGrid, GameInstance, GridCell};
+use crate::parser::Difficulty;
+
+// Chance that an easy opponent makes a random move instead of a sensible one
+const EASY_BLUNDER_CHANCE: f64 = 0.25;
+
+// Pick the next direction of the computer-controlled snake `player`
+pub fn choose_direction(
+    instance: &GameInstance,
+    player: usize,
+    difficulty: Difficulty,
+    rng: &mut impl Rng,
+) -> Direction {
+    let snake = &instance.snakes[player];
+    let grid = &instance.grid;
+    let head = snake.get_head();
+    let blocked = blocked_cells(instance);
+
+    // Turning back onto its own body is not possible
+    let candidates: Vec<Direction> = Direction::ALL
+        .into_iter()
+        .filter(|direction| direction.vertical() != snake.direction.vertical())
+        .chain([snake.direction])
+        .collect();
+    let safe: Vec<Direction> = candidates
+        .iter()
+        .copied()
+        .filter(|direction| !blocked.contains(&grid.next_cell(head, *direction)))
+        .collect();
+
+    match difficulty {
+        Difficulty::Easy if rng.gen_bool(EASY_BLUNDER_CHANCE) => *candidates.choose(rng).unwrap(),
+        Difficulty::Easy | Difficulty::Normal => {
+            // Greedily move towards the food, avoiding only immediate collisions
+            safe.iter()
+                .copied()
+                .min_by_key(|direction| {
+                    distance(grid, &grid.next_cell(head, *direction), &instance.food)
+                })
+                .unwrap_or(snake.direction)
+        }
+        Difficulty::Hard => {
+            // Follow the shortest path to the food, unless it leads into a dead end
+            let length = snake.body.len();
+            path_to_food(grid, head, &instance.food, &blocked)
+                .filter(|direction| {
+                    flood_fill(grid, &grid.next_cell(head, *direction), &blocked, length) >= length
+                })
+                .or_else(|| {
+                    safe.iter().copied().max_by_key(|direction| {
+                        flood_fill(grid, &grid.next_cell(head, *direction), &blocked, length)
+                    })
+                })
+                .unwrap_or(snake.direction)
+        }
+    }
+}
+
+// Cells occupied on the next tick; tails are left out since they move away
+fn blocked_cells(instance: &GameInstance) -> HashSet<GridCell> {
+    instance
+        .snakes
+        .iter()
+        .filter(|snake| snake.alive)
+        .flat_map(|snake| snake.body.range(..snake.body.len() - 1))
+        .copied()
+        .collect()
+}
+
+// Manhattan distance on a grid that wraps around at the edges
+fn distance(grid: &GameGrid, from: &GridCell, to: &GridCell) -> u16 {
+    let dx = from.x.abs_diff(to.x);
+    let dy = from.y.abs_diff(to.y);
+    dx.min(grid.width() - dx) + dy.min(grid.height() - dy)
+}
+
+// First step of the shortest path from `head` to `food`, if there is one
+fn path_to_food(
+    grid: &GameGrid,
+    head: &GridCell,
+    food: &GridCell,
+    blocked: &HashSet<GridCell>,
+) -> Option<Direction> {
+    let mut visited = HashSet::from([*head]);
+    let mut queue = VecDeque::new();
+    for direction in Direction::ALL {
+        let cell = grid.next_cell(head, direction);
+        if !blocked.contains(&cell) && visited.insert(cell) {
+            queue.push_back((cell, direction));
+        }
+    }
+    while let Some((cell, first_step)) = queue.pop_front() {
+        if cell == *food {
+            return Some(first_step);
+        }
+        for direction in Direction::ALL {
+            let next = grid.next_cell(&cell, direction);
+            if !blocked.contains(&next) && visited.insert(next) {
+                queue.push_back((next, first_step));
+            }
+        }
+    }
+    None
+}
+
+// Number of free cells reachable from `start`, counting at most `limit`
+fn flood_fill(
+    grid: &GameGrid,
+    start: &GridCell,
+    blocked: &HashSet<GridCell>,
+    limit: usize,
+) -> usize {
+    if blocked.contains(start) {
+        return 0;
+    }
+    let mut visited = HashSet::from([*start]);
+    let mut queue = VecDeque::from([*start]);
+    while let Some(cell) = queue.pop_front() {
+        if visited.len() >= limit {
+            break;
+        }
+        for direction in Direction::ALL {
+            let next = grid.next_cell(&cell, direction);
+            if !blocked.contains(&next) && visited.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    visited.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn cells(cells: &[(u16, u16)]) -> VecDeque<GridCell> {
+        cells.iter().map(|&(x, y)| GridCell { x, y }).collect()
+    }
+
+    // A 20x9 grid with the food at `food`, the snake of player 0 heading left from (14, 4) and
+    // a second snake over `other`, head first
+    fn instance(food: (u16, u16), other: &[(u16, u16)]) -> GameInstance {
+        let mut instance = GameInstance::with_players(GameGrid::with_dimensions(20, 9), 2);
+        instance.snakes[0].body = cells(&[(14, 4), (15, 4), (16, 4), (17, 4), (18, 4)]);
+        instance.snakes[0].direction = Direction::Left;
+        instance.snakes[1].body = cells(other);
+        instance.food = GridCell {
+            x: food.0,
+            y: food.1,
+        };
+        instance
+    }
+
+    fn choose(instance: &GameInstance, difficulty: Difficulty) -> Direction {
+        choose_direction(instance, 0, difficulty, &mut StdRng::seed_from_u64(0))
+    }
+
+    const FAR_AWAY: [(u16, u16); 3] = [(2, 8), (3, 8), (4, 8)];
+
+    #[test]
+    fn heads_for_the_food() {
+        let above = instance((14, 1), &FAR_AWAY);
+        let ahead = instance((9, 4), &FAR_AWAY);
+        let below = instance((15, 6), &FAR_AWAY);
+        for difficulty in [Difficulty::Normal, Difficulty::Hard] {
+            assert_eq!(choose(&above, difficulty), Direction::Up);
+            assert_eq!(choose(&ahead, difficulty), Direction::Left);
+            assert_eq!(choose(&below, difficulty), Direction::Down);
+        }
+    }
+
+    #[test]
+    fn avoids_running_into_another_snake() {
+        // A snake crosses the row right in front of the head, with the food behind it
+        let instance = instance((10, 4), &[(13, 3), (13, 4), (13, 5), (13, 6)]);
+        for difficulty in [Difficulty::Normal, Difficulty::Hard] {
+            let direction = choose(&instance, difficulty);
+            assert!(direction.vertical(), "{difficulty:?} went {direction:?}");
+        }
+    }
+
+    #[test]
+    fn avoids_running_into_itself() {
+        // Moving up with its own body to the left of the head, between it and the food
+        let mut instance = instance((10, 4), &FAR_AWAY);
+        instance.snakes[0].body = cells(&[(14, 4), (14, 5), (13, 5), (13, 4), (13, 3), (12, 3)]);
+        instance.snakes[0].direction = Direction::Up;
+        for difficulty in [Difficulty::Normal, Difficulty::Hard] {
+            assert_ne!(choose(&instance, difficulty), Direction::Left);
+        }
+    }
+}
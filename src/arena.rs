@@ -0,0 +1,393 @@
+use std::error::Error;
+use std::io::{stdin, stdout};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::IntoAlternateScreen;
+use termion::terminal_size;
+
+use crate::battlesnake::{self, Bot, GameInfo};
+use crate::error::GameError;
+use crate::game_input::{GameInput, KeyPress};
+use crate::game_instance::{Direction, GameGrid, GameInstance};
+use crate::game_output::GameOutput;
+use crate::parser::MovementKeyScheme;
+
+pub struct ArenaOptions {
+    pub bots: Vec<String>,
+    pub width: u16,
+    pub height: u16,
+    // Time a bot has to answer a request
+    pub timeout: Duration,
+    // Shortest time between two turns
+    pub tick: Duration,
+}
+
+// Plays Battlesnake bots against each other on this terminal. Every bot controls one snake.
+pub struct Arena {
+    bots: Vec<Bot>,
+    urls: Vec<String>,
+    options: ArenaOptions,
+    x_min: u16,
+    y_min: u16,
+    input: GameInput,
+    output: GameOutput,
+}
+
+// What happened to the bots of a finished round
+struct RoundStats {
+    turns: u32,
+    // Moves every bot failed to answer in time or correctly
+    missed: Vec<u32>,
+}
+
+impl Arena {
+    pub fn new(options: ArenaOptions) -> Result<Self, Box<dyn Error>> {
+        let bots = options
+            .bots
+            .iter()
+            .map(|url| Bot::from_url(url))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Every snake starts on its own row
+        let (width, height) = (options.width, options.height);
+        if (height as usize) < bots.len() + 2 {
+            return Err(format!(
+                "a board {height} cells high fits at most {} bots",
+                height - 2
+            )
+            .into());
+        }
+
+        // Centre the board, leaving room for the border
+        let (term_x, term_y) = terminal_size()?;
+        if term_x < width + 2 || term_y < height + 2 {
+            return Err(format!(
+                "terminal is too small for a {width}x{height} board, it needs at least {}x{}",
+                width + 2,
+                height + 2
+            )
+            .into());
+        }
+        let x_min = (term_x - width) / 2 + 1;
+        let y_min = (term_y - height) / 2 + 1;
+
+        let input = stdin().keys();
+        let input = GameInput::new(input, &[MovementKeyScheme::Arrows]);
+        let output = stdout().into_raw_mode()?.into_alternate_screen()?;
+        let output = GameOutput::new(output);
+        Ok(Self {
+            bots,
+            urls: options.bots.clone(),
+            options,
+            x_min,
+            y_min,
+            input,
+            output,
+        })
+    }
+
+    pub fn run(&mut self) -> Result<(), GameError> {
+        loop {
+            let instance = self.new_instance();
+            let info = GameInfo {
+                id: format!("rs_snake-{:016x}", rand::random::<u64>()),
+                timeout: self.options.timeout,
+            };
+            let Some((instance, stats)) = self.play_round(instance, &info)? else {
+                break;
+            };
+            if !self.show_results(&instance, &stats)? {
+                break;
+            }
+        }
+        self.output.clear_screen()?;
+        self.output.show_cursor()?;
+        self.output.render()
+    }
+
+    fn new_instance(&self) -> GameInstance {
+        let grid = GameGrid::with_origin(
+            self.x_min,
+            self.y_min,
+            self.options.width,
+            self.options.height,
+        );
+        GameInstance::with_players(grid, self.bots.len())
+    }
+
+    // Play until one bot is left, or none for a lone bot. Returns `None` when the user quits.
+    fn play_round(
+        &mut self,
+        mut instance: GameInstance,
+        info: &GameInfo,
+    ) -> Result<Option<(GameInstance, RoundStats)>, GameError> {
+        let mut stats = RoundStats {
+            turns: 0,
+            missed: vec![0; self.bots.len()],
+        };
+        self.for_each_bot(&instance, info, stats.turns, true, |bot, state, timeout| {
+            bot.start(state, timeout).ok()
+        });
+        self.output.clear_screen()?;
+        self.draw(&instance)?;
+
+        loop {
+            let turn_start = Instant::now();
+            for keypress in self.input.get_keypresses() {
+                let quit = match keypress {
+                    KeyPress::Pause => !self.pause(&instance)?,
+                    KeyPress::Quit => true,
+                    _ => false,
+                };
+                if quit {
+                    self.end_round(&instance, info, stats.turns);
+                    return Ok(None);
+                }
+            }
+
+            let moves = self.for_each_bot(
+                &instance,
+                info,
+                stats.turns,
+                false,
+                |bot, state, timeout| bot.choose_move(state, timeout).ok(),
+            );
+            apply_moves(&mut instance, moves, &mut stats.missed);
+
+            let alive: Vec<bool> = instance.snakes.iter().map(|s| s.alive).collect();
+            instance.game_cycle();
+            stats.turns += 1;
+
+            let survivors = instance.alive_count();
+            if survivors == 0 || (self.bots.len() > 1 && survivors == 1) {
+                self.end_round(&instance, info, stats.turns);
+                return Ok(Some((instance, stats)));
+            }
+            // Eliminated snakes leave the board, as in Battlesnake
+            for (player, was_alive) in alive.into_iter().enumerate() {
+                if was_alive && !instance.snakes[player].alive {
+                    self.output.undraw_snake(&instance.snakes[player])?;
+                }
+            }
+            self.draw(&instance)?;
+            thread::sleep(self.options.tick.saturating_sub(turn_start.elapsed()));
+        }
+    }
+
+    // Hold the round until 'p' is pressed again, like the game does. Returns false if the user
+    // quits instead.
+    fn pause(&mut self, instance: &GameInstance) -> Result<bool, GameError> {
+        let (x_min, y_min, x_max, y_max) = instance.grid.get_corners();
+        // Boards can be as narrow as 7 cells
+        let message = match instance.grid.width() {
+            30.. => "Paused - press 'p' to continue",
+            _ => "Paused",
+        };
+        self.output
+            .draw_board_message(x_min, x_max, (y_min + y_max) / 2, message)?;
+        self.output.render()?;
+        let resume = loop {
+            match self.input.wait_for_keypress(None) {
+                KeyPress::Pause => break true,
+                KeyPress::Quit => break false,
+                _ => (),
+            }
+        };
+        self.output.clear_screen()?;
+        self.draw(instance)?;
+        Ok(resume)
+    }
+
+    // Every bot hears about the end of the game, including the eliminated ones
+    fn end_round(&self, instance: &GameInstance, info: &GameInfo, turn: u32) {
+        self.for_each_bot(instance, info, turn, true, |bot, state, timeout| {
+            bot.end(state, timeout).ok()
+        });
+    }
+
+    // Send a request to every bot at once, or only to the bots of living snakes unless
+    // `eliminated` is set, collecting the replies
+    fn for_each_bot<T: Send>(
+        &self,
+        instance: &GameInstance,
+        info: &GameInfo,
+        turn: u32,
+        eliminated: bool,
+        request: impl Fn(&Bot, &serde_json::Value, Duration) -> Option<T> + Sync,
+    ) -> Vec<Option<T>> {
+        let timeout = self.options.timeout;
+        ask_bots(
+            &self.bots, instance, info, turn, eliminated, timeout, request,
+        )
+    }
+
+    // Show the outcome of a round. Returns whether the user wants another one.
+    fn show_results(
+        &mut self,
+        instance: &GameInstance,
+        stats: &RoundStats,
+    ) -> Result<bool, GameError> {
+        let winner = match instance.alive_count() {
+            1 if self.bots.len() > 1 => instance.snakes.iter().position(|snake| snake.alive),
+            _ => None,
+        };
+        let title = match winner {
+            Some(player) => format!("bot {}|wins!", player + 1),
+            None if self.bots.len() == 1 => String::from("game|over!"),
+            None => String::from("it's a|draw!"),
+        };
+        let mut results = vec![format!("{} turns", stats.turns)];
+        results.extend(instance.snakes.iter().enumerate().map(|(player, snake)| {
+            format!(
+                "bot {} ({}): length {}, food {}, missed moves {}",
+                player + 1,
+                self.urls[player],
+                snake.body.len(),
+                snake.food_eaten,
+                stats.missed[player]
+            )
+        }));
+        self.output.clear_screen()?;
+        self.output.draw_winner_message(&title, &results)?;
+        self.output.render()?;
+
+        self.input.empty_key_buffer();
+        loop {
+            match self.input.wait_for_keypress(None) {
+                KeyPress::Pause => return Ok(true),
+                KeyPress::Quit => return Ok(false),
+                _ => (),
+            }
+        }
+    }
+
+    fn draw(&mut self, instance: &GameInstance) -> Result<(), GameError> {
+        let (x_min, y_min, x_max, y_max) = instance.grid.get_corners();
+        self.output.draw_border(x_min, x_max, y_min, y_max)?;
+        let living = instance.snakes.iter().enumerate();
+        self.output
+            .draw_snakes(living.filter(|(_, snake)| snake.alive))?;
+        self.output.draw_food(&instance.food)?;
+        self.output.render()
+    }
+}
+
+// Send `request` to every bot on its own thread, or only to the bots of living snakes unless
+// `eliminated` is set. Bots that were not asked or failed have no reply.
+fn ask_bots<T: Send>(
+    bots: &[Bot],
+    instance: &GameInstance,
+    info: &GameInfo,
+    turn: u32,
+    eliminated: bool,
+    timeout: Duration,
+    request: impl Fn(&Bot, &serde_json::Value, Duration) -> Option<T> + Sync,
+) -> Vec<Option<T>> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = bots
+            .iter()
+            .enumerate()
+            .map(|(player, bot)| {
+                let request = &request;
+                let asked = eliminated || instance.snakes[player].alive;
+                let state = battlesnake::game_state(instance, info, turn, player);
+                scope.spawn(move || {
+                    if asked {
+                        request(bot, &state, timeout)
+                    } else {
+                        None
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap_or(None))
+            .collect()
+    })
+}
+
+// Turn the snakes of the bots that answered. A bot that did not answer in time or correctly
+// keeps going straight, which counts as a missed move while its snake is alive.
+fn apply_moves(instance: &mut GameInstance, moves: Vec<Option<Direction>>, missed: &mut [u32]) {
+    for (player, direction) in moves.into_iter().enumerate() {
+        match direction {
+            Some(direction) => instance.turn(player, direction),
+            None if instance.snakes[player].alive => missed[player] += 1,
+            None => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::battlesnake::tests::stub_bot;
+    use crate::game_instance::DeathCause;
+
+    #[test]
+    fn bots_that_time_out_keep_going_straight() {
+        let (answering, _) = stub_bot(Some(
+            "HTTP/1.1 200 OK\r\nContent-Length: 13\r\n\r\n{\"move\":\"up\"}",
+        ));
+        let (silent, _) = stub_bot(None);
+        let bots = [
+            Bot::from_url(&answering).unwrap(),
+            Bot::from_url(&silent).unwrap(),
+        ];
+        let mut instance = GameInstance::with_players(GameGrid::with_dimensions(11, 11), 2);
+        let info = GameInfo {
+            id: String::from("test"),
+            timeout: Duration::from_millis(100),
+        };
+
+        let start = Instant::now();
+        let moves = ask_bots(
+            &bots,
+            &instance,
+            &info,
+            0,
+            false,
+            info.timeout,
+            |bot, state, timeout| bot.choose_move(state, timeout).ok(),
+        );
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(moves, [Some(Direction::Up), None]);
+
+        let mut missed = [0, 0];
+        apply_moves(&mut instance, moves, &mut missed);
+        assert_eq!(instance.snakes[0].direction, Direction::Up);
+        assert_eq!(instance.snakes[1].direction, Direction::Right);
+        assert_eq!(missed, [0, 1]);
+    }
+
+    #[test]
+    fn eliminated_bots_are_only_asked_when_told_to() {
+        let (url, requests) = stub_bot(Some("HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{}"));
+        let bots = [Bot::from_url(&url).unwrap()];
+        let mut instance = GameInstance::with_players(GameGrid::with_dimensions(11, 11), 1);
+        instance.kill(0, DeathCause::Timeout);
+        let info = GameInfo {
+            id: String::from("test"),
+            timeout: Duration::from_secs(5),
+        };
+        let ask = |eliminated| {
+            ask_bots(
+                &bots,
+                &instance,
+                &info,
+                3,
+                eliminated,
+                info.timeout,
+                |bot, state, timeout| bot.end(state, timeout).ok(),
+            )
+        };
+        assert_eq!(ask(false), [None]);
+        assert!(requests.try_recv().is_err());
+        assert_eq!(ask(true), [Some(())]);
+        assert_eq!(requests.recv().unwrap().0, "POST /snake/end HTTP/1.1");
+    }
+}
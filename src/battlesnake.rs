@@ -0,0 +1,509 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::game_instance::{Direction, GameInstance, GridCell, Snake};
+
+// Bots are told the board wraps around, which is how the game's grid behaves
+const RULESET: &str = "wrapped";
+const RULESET_VERSION: &str = "v1.2.3";
+// Snakes here never starve, so every snake reports full health
+const HEALTH: u32 = 100;
+const HTTP_PREFIX: &str = "http://";
+// Largest reply body accepted from a bot; a move fits in a few dozen bytes
+const MAX_BODY: usize = 64 * 1024;
+// Largest status line and headers accepted from a bot
+const MAX_HEAD: u64 = 16 * 1024;
+
+// A bot implementing the Battlesnake HTTP API, see https://docs.battlesnake.com/api
+pub struct Bot {
+    // `host:port` the bot listens on
+    host: String,
+    // Path prefix of the bot's endpoints, without a trailing slash
+    path: String,
+}
+
+// Game-wide values sent to the bots with every request
+pub struct GameInfo {
+    pub id: String,
+    pub timeout: Duration,
+}
+
+impl Bot {
+    // Parse a bot URL such as http://127.0.0.1:8000 or http://localhost:8000/snake
+    pub fn from_url(url: &str) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix(HTTP_PREFIX)
+            .ok_or_else(|| format!("bot URL '{url}' must start with {HTTP_PREFIX}"))?;
+        let (host, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, ""),
+        };
+        if host.is_empty() {
+            return Err(format!("bot URL '{url}' has no host"));
+        }
+        let host = if host.contains(':') {
+            host.to_string()
+        } else {
+            format!("{host}:80")
+        };
+        Ok(Self {
+            host,
+            path: path.trim_end_matches('/').to_string(),
+        })
+    }
+
+    // Tell the bot that a game begins. Its reply is ignored.
+    pub fn start(&self, state: &Value, timeout: Duration) -> io::Result<()> {
+        self.post("/start", state, timeout).map(|_| ())
+    }
+
+    // Ask the bot for its next move
+    pub fn choose_move(&self, state: &Value, timeout: Duration) -> io::Result<Direction> {
+        let reply = self.post("/move", state, timeout)?;
+        decode_move(&reply).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("bot sent an invalid move: {reply}"),
+            )
+        })
+    }
+
+    // Tell the bot that the game is over. Its reply is ignored.
+    pub fn end(&self, state: &Value, timeout: Duration) -> io::Result<()> {
+        self.post("/end", state, timeout).map(|_| ())
+    }
+
+    // Send a JSON body and return the body of the reply, all within `timeout`
+    fn post(&self, endpoint: &str, body: &Value, timeout: Duration) -> io::Result<String> {
+        let deadline = Instant::now() + timeout;
+        let addr = self
+            .host
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "bot host not found"))?;
+        let stream = TcpStream::connect_timeout(&addr, timeout)?;
+        stream.set_nodelay(true)?;
+        let mut stream = DeadlineStream { stream, deadline };
+
+        let body = body.to_string();
+        let request = format!(
+            "POST {}{endpoint} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n{body}",
+            self.path,
+            self.host,
+            body.len(),
+        );
+        stream.write_all(request.as_bytes())?;
+        read_response(BufReader::new(stream))
+    }
+}
+
+// A connection whose reads and writes fail once `deadline` has passed, so that a bot
+// answering a byte at a time cannot stretch a request beyond its timeout
+struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl DeadlineStream {
+    fn remaining(&self) -> io::Result<Duration> {
+        self.deadline
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| !remaining.is_zero())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "bot did not answer in time"))
+    }
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.set_read_timeout(Some(self.remaining()?))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for DeadlineStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.set_write_timeout(Some(self.remaining()?))?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+// Read an HTTP/1.1 response, failing unless its status is 2xx or when it is larger than a bot
+// has any reason to send
+fn read_response(reader: impl BufRead) -> io::Result<String> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let too_large = || invalid(&format!("reply is larger than {MAX_BODY} bytes"));
+    // Bounds the head here and the body below, which is checked against MAX_BODY itself
+    let mut reader = reader.take(MAX_HEAD);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status: u16 = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| invalid("malformed HTTP status line"))?;
+
+    let mut content_length = None;
+    let mut chunked = false;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("connection closed inside the HTTP headers"));
+        }
+        if !line.ends_with('\n') && reader.limit() == 0 {
+            return Err(invalid("HTTP headers are too large"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse::<usize>().ok();
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = value.eq_ignore_ascii_case("chunked");
+        }
+    }
+
+    // Room for the body plus the chunk sizes and line breaks around it
+    reader.set_limit(2 * MAX_BODY as u64);
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            let size = line.trim_end().split(';').next().unwrap_or("");
+            let size = usize::from_str_radix(size, 16).map_err(|_| invalid("bad chunk size"))?;
+            if size == 0 {
+                break;
+            }
+            if size > MAX_BODY - body.len() {
+                return Err(too_large());
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..])?;
+            // Line break after the chunk data
+            line.clear();
+            reader.read_line(&mut line)?;
+        }
+    } else if let Some(length) = content_length {
+        if length > MAX_BODY {
+            return Err(too_large());
+        }
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    } else {
+        reader.set_limit(MAX_BODY as u64 + 1);
+        reader.read_to_end(&mut body)?;
+        if body.len() > MAX_BODY {
+            return Err(too_large());
+        }
+    }
+
+    if !(200..300).contains(&status) {
+        return Err(invalid(&format!("bot replied with HTTP status {status}")));
+    }
+    String::from_utf8(body).map_err(|_| invalid("reply is not valid UTF-8"))
+}
+
+// Battlesnake coordinates start at the bottom-left cell of the board, with y pointing up
+fn encode_cell(cell: &GridCell, instance: &GameInstance) -> Value {
+    let grid = &instance.grid;
+    json!({
+        "x": cell.x - grid.x_min,
+        "y": grid.y_max - cell.y,
+    })
+}
+
+fn encode_snake(player: usize, snake: &Snake, instance: &GameInstance) -> Value {
+    let body: Vec<Value> = snake
+        .body
+        .iter()
+        .map(|cell| encode_cell(cell, instance))
+        .collect();
+    json!({
+        "id": format!("snake-{player}"),
+        "name": format!("bot {}", player + 1),
+        "health": HEALTH,
+        "head": body[0],
+        "length": body.len(),
+        "body": body,
+        "latency": "0",
+        "shout": "",
+        "customizations": {
+            "color": "#888888",
+            "head": "default",
+            "tail": "default",
+        },
+    })
+}
+
+// The game state as seen by the bot controlling `you`, in the format of the Battlesnake API
+pub fn game_state(instance: &GameInstance, info: &GameInfo, turn: u32, you: usize) -> Value {
+    let snakes: Vec<Value> = instance
+        .snakes
+        .iter()
+        .enumerate()
+        .filter(|(player, snake)| snake.alive || *player == you)
+        .map(|(player, snake)| encode_snake(player, snake, instance))
+        .collect();
+    json!({
+        "game": {
+            "id": info.id,
+            "ruleset": {
+                "name": RULESET,
+                "version": RULESET_VERSION,
+                "settings": {
+                    "foodSpawnChance": 100,
+                    "minimumFood": 1,
+                    "hazardDamagePerTurn": 0,
+                },
+            },
+            "map": "standard",
+            "timeout": info.timeout.as_millis() as u64,
+            "source": "custom",
+        },
+        "turn": turn,
+        "board": {
+            "width": instance.grid.width(),
+            "height": instance.grid.height(),
+            "food": [encode_cell(&instance.food, instance)],
+            "hazards": [],
+            "snakes": snakes,
+        },
+        "you": encode_snake(you, &instance.snakes[you], instance),
+    })
+}
+
+// Read the direction out of a /move reply such as {"move": "up", "shout": "..."}
+pub fn decode_move(reply: &str) -> Option<Direction> {
+    let reply: Value = serde_json::from_str(reply).ok()?;
+    // Up on the Battlesnake board is up on the screen as well, since y is flipped
+    match reply.get("move")?.as_str()? {
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::{Cursor, Read};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+
+    use super::*;
+    use crate::game_instance::GameGrid;
+
+    // A bot on loopback that answers every request with `response`, or never answers without
+    // one. Returns its URL and the request line and body of every request it gets.
+    pub(crate) fn stub_bot(response: Option<&'static str>) -> (String, Receiver<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/snake", listener.local_addr().unwrap());
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || {
+            // Silent bots keep their connections open
+            let mut held = Vec::new();
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim_end().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = header.strip_prefix("Content-Length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let body = String::from_utf8(body).unwrap();
+                let _ = sender.send((request_line.trim_end().to_string(), body));
+                match response {
+                    Some(response) => stream.write_all(response.as_bytes()).unwrap(),
+                    None => held.push(stream),
+                }
+            }
+        });
+        (url, requests)
+    }
+
+    fn response(head: &str, body: &str) -> String {
+        format!("{head}\r\n\r\n{body}")
+    }
+
+    #[test]
+    fn bodies_with_a_content_length_are_read() {
+        let reply = response(
+            "HTTP/1.1 200 OK\r\nContent-Length: 15",
+            "{\"move\":\"up\"}\n\nextra",
+        );
+        let body = read_response(Cursor::new(reply)).unwrap();
+        assert_eq!(body, "{\"move\":\"up\"}\n\n");
+    }
+
+    #[test]
+    fn chunked_bodies_are_joined() {
+        let reply = response(
+            "HTTP/1.1 200 OK\r\ntransfer-encoding: Chunked",
+            "5\r\n{\"mov\r\n9;ext=1\r\ne\": \"left\r\n2\r\n\"}\r\n0\r\n\r\n",
+        );
+        let body = read_response(Cursor::new(reply)).unwrap();
+        assert_eq!(body, "{\"move\": \"left\"}");
+    }
+
+    #[test]
+    fn bodies_without_a_length_end_with_the_connection() {
+        let reply = response("HTTP/1.0 200 OK\r\nContent-Type: application/json", "{}");
+        assert_eq!(read_response(Cursor::new(reply)).unwrap(), "{}");
+    }
+
+    #[test]
+    fn error_statuses_fail() {
+        let reply = response(
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 4",
+            "oops",
+        );
+        let err = read_response(Cursor::new(reply)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("500"));
+        assert!(read_response(Cursor::new("garbage\r\n\r\n")).is_err());
+    }
+
+    #[test]
+    fn oversized_replies_fail() {
+        let large = "x".repeat(MAX_BODY + 1);
+        let replies = [
+            response(
+                &format!("HTTP/1.1 200 OK\r\nContent-Length: {}", large.len()),
+                &large,
+            ),
+            response("HTTP/1.1 200 OK\r\nContent-Length: 99999999999", ""),
+            response("HTTP/1.0 200 OK", &large),
+            response(
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked",
+                &format!("{:x}\r\n{large}\r\n0\r\n\r\n", large.len()),
+            ),
+            response(&format!("HTTP/1.1 200 OK\r\nX-Padding: {large}"), "{}"),
+        ];
+        for reply in replies {
+            let err = read_response(Cursor::new(reply)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        // Exactly the limit is fine
+        let body = "x".repeat(MAX_BODY);
+        let reply = response("HTTP/1.0 200 OK", &body);
+        assert_eq!(read_response(Cursor::new(reply)).unwrap(), body);
+    }
+
+    #[test]
+    fn moves_are_decoded() {
+        assert_eq!(decode_move(r#"{"move": "up"}"#), Some(Direction::Up));
+        assert_eq!(
+            decode_move(r#"{"move": "down", "shout": "hi"}"#),
+            Some(Direction::Down)
+        );
+        assert_eq!(decode_move(r#"{"move": "left"}"#), Some(Direction::Left));
+        assert_eq!(decode_move(r#"{"move": "right"}"#), Some(Direction::Right));
+        assert_eq!(decode_move(r#"{"move": "sideways"}"#), None);
+        assert_eq!(decode_move(r#"{"shout": "up"}"#), None);
+        assert_eq!(decode_move("up"), None);
+    }
+
+    #[test]
+    fn game_state_flips_the_y_axis() {
+        // The grid's top-left cell is at (5, 3) on the screen
+        let grid = GameGrid::with_origin(5, 3, 11, 11);
+        let mut instance = GameInstance::with_players(grid, 2);
+        instance.food = GridCell { x: 7, y: 3 };
+        let info = GameInfo {
+            id: String::from("test"),
+            timeout: Duration::from_millis(500),
+        };
+        let state = game_state(&instance, &info, 4, 1);
+        assert_eq!(state["turn"], 4);
+        assert_eq!(state["board"]["food"][0], json!({"x": 2, "y": 10}));
+        let head = instance.snakes[1].get_head();
+        let expected = json!({"x": head.x - 5, "y": 13 - head.y});
+        assert_eq!(state["you"]["head"], expected);
+        assert_eq!(state["board"]["snakes"][1]["head"], expected);
+        assert_eq!(state["you"]["id"], "snake-1");
+    }
+
+    #[test]
+    fn bots_are_asked_over_http() {
+        let (url, requests) = stub_bot(Some(
+            "HTTP/1.1 200 OK\r\nContent-Length: 28\r\n\r\n{\"move\":\"down\",\"shout\":\"\"}\n\n",
+        ));
+        let bot = Bot::from_url(&url).unwrap();
+        let state = json!({"turn": 1});
+        let direction = bot.choose_move(&state, Duration::from_secs(5)).unwrap();
+        assert_eq!(direction, Direction::Down);
+        let (request_line, body) = requests.recv().unwrap();
+        assert_eq!(request_line, "POST /snake/move HTTP/1.1");
+        assert_eq!(body, state.to_string());
+    }
+
+    #[test]
+    fn silent_bots_time_out() {
+        let (url, _requests) = stub_bot(None);
+        let bot = Bot::from_url(&url).unwrap();
+        let start = std::time::Instant::now();
+        let err = bot
+            .choose_move(&json!({}), Duration::from_millis(100))
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn slow_replies_time_out_as_a_whole() {
+        // A bot that sends its reply a byte at a time, each well within the timeout
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            for byte in b"HTTP/1.1 200 OK\r\nContent-Length: 15\r\n\r\n{\"move\":\"up\"}" {
+                if stream.write_all(&[*byte]).is_err() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+        });
+        let bot = Bot::from_url(&url).unwrap();
+        let start = std::time::Instant::now();
+        let err = bot
+            .choose_move(&json!({}), Duration::from_millis(200))
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ));
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,84 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::panic;
+use std::path::PathBuf;
+use std::process;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use termion::cursor;
+use termion::screen::ToMainScreen;
+
+// Terminal settings from before the game switched to raw mode
+static ORIGINAL_TERMIOS: OnceLock<libc::termios> = OnceLock::new();
+// Message and location of the last panic, for the crash report
+static PANIC_MESSAGE: Mutex<Option<String>> = Mutex::new(None);
+
+// Remember the terminal settings and restore them if the program panics, is interrupted or is
+// terminated. Call before switching the terminal to raw mode.
+pub fn install() -> io::Result<()> {
+    // SAFETY: tcgetattr fills in the zeroed struct, which is only kept if the call succeeds
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDOUT_FILENO, &mut termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let _ = ORIGINAL_TERMIOS.set(termios);
+
+    // The default hook prints the message, which is only readable once the terminal is restored
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        *PANIC_MESSAGE.lock().unwrap_or_else(PoisonError::into_inner) = Some(info.to_string());
+        default_hook(info);
+    }));
+
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            restore_terminal();
+            // Shells report a death by signal as 128 plus the signal number
+            process::exit(128 + signal);
+        }
+    });
+    Ok(())
+}
+
+// Switch back to cooked mode and the main screen, and show the cursor
+pub fn restore_terminal() {
+    let Some(termios) = ORIGINAL_TERMIOS.get() else {
+        return;
+    };
+    // Write straight to the file descriptor, since the panicking thread may hold the stdout lock
+    let reset = format!("{ToMainScreen}{}", cursor::Show);
+    // SAFETY: both calls only read from memory that outlives them
+    unsafe {
+        libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, termios);
+        libc::write(libc::STDOUT_FILENO, reset.as_ptr().cast(), reset.len());
+    }
+}
+
+// Write the last panic message and `details` about the game to a new file in the temporary
+// directory, returning its path. The process id keeps reports of games that crash in the same
+// second apart.
+pub fn write_report(details: &str) -> io::Result<PathBuf> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let name = format!("rs_snake-crash-{time}-{}.txt", process::id());
+    let path = env::temp_dir().join(name);
+    let message = PANIC_MESSAGE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+        .unwrap_or_else(|| String::from("no panic message"));
+    let report = format!(
+        "rs_snake {} crashed\n\n{message}\n\n{details}",
+        env!("CARGO_PKG_VERSION")
+    );
+    fs::write(&path, report)?;
+    Ok(path)
+}
@@ -0,0 +1,515 @@
+use std::thread;
+
+use crate::game_instance::{DeathCause, Direction, GameGrid, GameInstance, GridCell, TickOutcome};
+
+// Channels of the grid observation: snake head, snake body, food
+const GRID_CHANNELS: usize = 3;
+// Channels of the ego-centric observation: snake body, food
+const EGO_CHANNELS: usize = 2;
+// Danger ahead/left/right, direction one-hot, food offset, snake length
+const FEATURE_LEN: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObservationKind {
+    // Full board as a [channel, y, x] tensor
+    Grid,
+    // Square window of side 2 * radius + 1 centred on the head, rotated so the snake faces up
+    EgoCentric { radius: u16 },
+    // Small hand-crafted feature vector
+    Features,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RewardConfig {
+    // Reward for eating a piece of food
+    pub food: f32,
+    // Reward for dying
+    pub death: f32,
+    // Reward given on every step, usually a small negative number
+    pub step: f32,
+    // Reward per cell the head moved closer to the food (negative when moving away)
+    pub approach: f32,
+}
+
+impl Default for RewardConfig {
+    fn default() -> Self {
+        Self {
+            food: 1.0,
+            death: -1.0,
+            step: 0.0,
+            approach: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnvConfig {
+    pub width: u16,
+    pub height: u16,
+    pub observation: ObservationKind,
+    pub reward: RewardConfig,
+    // Episode is truncated after this many steps
+    pub max_steps: Option<u64>,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            width: 20,
+            height: 20,
+            observation: ObservationKind::Grid,
+            reward: RewardConfig::default(),
+            max_steps: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepInfo {
+    pub length: usize,
+    pub steps: u64,
+    pub ate_food: bool,
+    pub truncated: bool,
+    // The snake filled the whole grid, which ends the episode
+    pub won: bool,
+    // Why the snake died, if it did
+    pub death: Option<DeathCause>,
+}
+
+pub struct SnakeEnv {
+    config: EnvConfig,
+    instance: GameInstance,
+    steps: u64,
+    done: bool,
+}
+
+impl SnakeEnv {
+    pub fn new(config: EnvConfig) -> Self {
+        assert!(
+            GameGrid::fits(config.width, config.height),
+            "environment must be at least {}x{} and at most {} cells",
+            GameGrid::MIN_WIDTH,
+            GameGrid::MIN_HEIGHT,
+            GameGrid::MAX_CELLS
+        );
+        let grid = GameGrid::with_dimensions(config.width, config.height);
+        Self {
+            config,
+            instance: GameInstance::with_seed(grid, 0),
+            steps: 0,
+            done: false,
+        }
+    }
+
+    pub fn config(&self) -> &EnvConfig {
+        &self.config
+    }
+
+    pub fn instance(&self) -> &GameInstance {
+        &self.instance
+    }
+
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        let grid = GameGrid::with_dimensions(self.config.width, self.config.height);
+        self.instance = GameInstance::with_seed(grid, seed);
+        self.steps = 0;
+        self.done = false;
+        self.observe()
+    }
+
+    pub fn step(&mut self, action: Direction) -> (Observation, f32, bool, StepInfo) {
+        assert!(
+            !self.done,
+            "step called on a finished episode, call reset first"
+        );
+        let reward_config = self.config.reward;
+        let distance = self.food_distance();
+
+        self.instance.turn(0, action);
+        let tick = self.instance.game_cycle();
+        let death = match tick.outcomes[0] {
+            Some(TickOutcome::Died(cause)) => Some(cause),
+            _ => None,
+        };
+        let alive = death.is_none();
+        let won = tick.board_full;
+        self.steps += 1;
+
+        let ate_food = tick.outcomes[0] == Some(TickOutcome::AteFood);
+        let mut reward = reward_config.step;
+        if !alive {
+            reward += reward_config.death;
+        } else if ate_food {
+            reward += reward_config.food;
+        } else {
+            reward += reward_config.approach * (distance as f32 - self.food_distance() as f32);
+        }
+
+        let truncated = alive && !won && self.config.max_steps.is_some_and(|max| self.steps >= max);
+        self.done = !alive || won || truncated;
+        let info = StepInfo {
+            length: self.instance.snakes[0].body.len(),
+            steps: self.steps,
+            ate_food,
+            truncated,
+            won,
+            death,
+        };
+        (self.observe(), reward, self.done, info)
+    }
+
+    pub fn observe(&self) -> Observation {
+        match self.config.observation {
+            ObservationKind::Grid => self.observe_grid(),
+            ObservationKind::EgoCentric { radius } => self.observe_ego_centric(radius),
+            ObservationKind::Features => self.observe_features(),
+        }
+    }
+
+    fn observe_grid(&self) -> Observation {
+        let (width, height) = (self.config.width as usize, self.config.height as usize);
+        let mut data = vec![0.0; GRID_CHANNELS * width * height];
+        let index = |channel: usize, cell: &GridCell| {
+            channel * width * height + cell.y as usize * width + cell.x as usize
+        };
+        for segment in self.instance.snakes[0].body.range(1..) {
+            data[index(1, segment)] = 1.0;
+        }
+        data[index(0, self.instance.snakes[0].get_head())] = 1.0;
+        data[index(2, &self.instance.food)] = 1.0;
+        Observation {
+            shape: vec![GRID_CHANNELS, height, width],
+            data,
+        }
+    }
+
+    fn observe_ego_centric(&self, radius: u16) -> Observation {
+        let side = 2 * radius as usize + 1;
+        let mut data = vec![0.0; EGO_CHANNELS * side * side];
+        let body = &self.instance.snakes[0].body;
+        let head = self.instance.snakes[0].get_head();
+        let radius = radius as i32;
+        for row in 0..side {
+            for col in 0..side {
+                // Offset relative to the head, with "up" meaning the snake's heading
+                let (forward, right) = (radius - row as i32, col as i32 - radius);
+                let cell = self.relative_cell(head, forward, right);
+                if body.range(1..).any(|segment| *segment == cell) {
+                    data[row * side + col] = 1.0;
+                }
+                if self.instance.food == cell {
+                    data[side * side + row * side + col] = 1.0;
+                }
+            }
+        }
+        Observation {
+            shape: vec![EGO_CHANNELS, side, side],
+            data,
+        }
+    }
+
+    fn observe_features(&self) -> Observation {
+        let head = self.instance.snakes[0].get_head();
+        let danger = |forward, right| {
+            let cell = self.relative_cell(head, forward, right);
+            // The tail moves away on the next tick, so it is not dangerous
+            let body = &self.instance.snakes[0].body;
+            let hit = body
+                .range(1..body.len() - 1)
+                .any(|segment| *segment == cell);
+            hit as u8 as f32
+        };
+        let (dx, dy) = self.food_offset();
+        let direction = self.instance.snakes[0].direction;
+        let data = vec![
+            danger(1, 0),
+            danger(0, -1),
+            danger(0, 1),
+            (direction == Direction::Up) as u8 as f32,
+            (direction == Direction::Down) as u8 as f32,
+            (direction == Direction::Left) as u8 as f32,
+            (direction == Direction::Right) as u8 as f32,
+            dx as f32 / self.config.width as f32,
+            dy as f32 / self.config.height as f32,
+            self.instance.snakes[0].body.len() as f32 / self.instance.grid.cells.len() as f32,
+        ];
+        Observation {
+            shape: vec![FEATURE_LEN],
+            data,
+        }
+    }
+
+    // Cell at the given offset from `origin`, measured in the snake's frame of reference
+    fn relative_cell(&self, origin: &GridCell, forward: i32, right: i32) -> GridCell {
+        let (dx, dy) = match self.instance.snakes[0].direction {
+            Direction::Up => (right, -forward),
+            Direction::Down => (-right, forward),
+            Direction::Left => (-forward, -right),
+            Direction::Right => (forward, right),
+        };
+        let (width, height) = (self.config.width as i32, self.config.height as i32);
+        // The board wraps around at the edges
+        GridCell {
+            x: (origin.x as i32 + dx).rem_euclid(width) as u16,
+            y: (origin.y as i32 + dy).rem_euclid(height) as u16,
+        }
+    }
+
+    // Shortest offset from the head to the food, taking wrap-around into account
+    fn food_offset(&self) -> (i32, i32) {
+        let head = self.instance.snakes[0].get_head();
+        let food = &self.instance.food;
+        let wrap = |delta: i32, size: i32| {
+            let delta = delta.rem_euclid(size);
+            if delta > size / 2 {
+                delta - size
+            } else {
+                delta
+            }
+        };
+        (
+            wrap(food.x as i32 - head.x as i32, self.config.width as i32),
+            wrap(food.y as i32 - head.y as i32, self.config.height as i32),
+        )
+    }
+
+    fn food_distance(&self) -> u32 {
+        let (dx, dy) = self.food_offset();
+        dx.unsigned_abs() + dy.unsigned_abs()
+    }
+}
+
+// A batch of independent environments stepped in parallel on scoped threads
+pub struct VecEnv {
+    envs: Vec<SnakeEnv>,
+}
+
+impl VecEnv {
+    pub fn new(config: EnvConfig, num_envs: usize) -> Self {
+        Self {
+            envs: (0..num_envs).map(|_| SnakeEnv::new(config)).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    pub fn envs(&self) -> &[SnakeEnv] {
+        &self.envs
+    }
+
+    // Reset every environment, the i-th one with `seed + i`
+    pub fn reset(&mut self, seed: u64) -> Vec<Observation> {
+        self.envs
+            .iter_mut()
+            .enumerate()
+            .map(|(i, env)| env.reset(seed.wrapping_add(i as u64)))
+            .collect()
+    }
+
+    pub fn reset_env(&mut self, index: usize, seed: u64) -> Observation {
+        self.envs[index].reset(seed)
+    }
+
+    // Step every environment with its own action; finished environments must be reset by the caller
+    pub fn step(&mut self, actions: &[Direction]) -> Vec<(Observation, f32, bool, StepInfo)> {
+        assert_eq!(actions.len(), self.envs.len(), "one action per environment");
+        let workers = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_size = self.envs.len().div_ceil(workers).max(1);
+        thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .envs
+                .chunks_mut(chunk_size)
+                .zip(actions.chunks(chunk_size))
+                .map(|(envs, actions)| {
+                    scope.spawn(move || {
+                        envs.iter_mut()
+                            .zip(actions)
+                            .map(|(env, action)| env.step(*action))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 10x10 board has the snake on row 4 heading left, its head at x = 4 and tail at x = 8
+    fn env(config: EnvConfig) -> SnakeEnv {
+        let mut env = SnakeEnv::new(EnvConfig {
+            width: 10,
+            height: 10,
+            ..config
+        });
+        env.reset(1);
+        env
+    }
+
+    #[test]
+    fn reset_is_deterministic() {
+        let (mut a, mut b) = (env(EnvConfig::default()), env(EnvConfig::default()));
+        assert_eq!(a.reset(42), b.reset(42));
+        for action in [
+            Direction::Left,
+            Direction::Up,
+            Direction::Right,
+            Direction::Up,
+        ] {
+            assert_eq!(a.step(action), b.step(action));
+        }
+        assert_eq!(a.instance().food, b.instance().food);
+    }
+
+    #[test]
+    fn eating_food_is_rewarded() {
+        let mut env = env(EnvConfig::default());
+        env.instance.food = GridCell { x: 3, y: 4 };
+        let (_, reward, done, info) = env.step(Direction::Left);
+        assert_eq!(reward, 1.0);
+        assert!(info.ate_food && !done);
+        assert_eq!(info.length, 6);
+    }
+
+    #[test]
+    fn dying_is_penalised() {
+        let mut env = env(EnvConfig::default());
+        // Coil the snake so that turning right runs into its own body
+        let snake = &mut env.instance.snakes[0];
+        snake.body = [(4, 4), (4, 5), (5, 5), (5, 4), (6, 4)]
+            .map(|(x, y)| GridCell { x, y })
+            .into();
+        snake.direction = Direction::Up;
+        env.instance.food = GridCell { x: 0, y: 0 };
+        let (_, reward, done, info) = env.step(Direction::Right);
+        assert_eq!(reward, -1.0);
+        assert!(done);
+        assert_eq!(info.death, Some(DeathCause::OwnBody));
+    }
+
+    #[test]
+    fn approaching_food_is_rewarded() {
+        let reward = RewardConfig {
+            step: -0.25,
+            approach: 0.5,
+            ..RewardConfig::default()
+        };
+        let mut env = env(EnvConfig {
+            reward,
+            ..EnvConfig::default()
+        });
+        env.instance.food = GridCell { x: 1, y: 4 };
+        assert_eq!(env.step(Direction::Left).1, 0.25);
+        // Moving up leaves the same row as the food
+        assert_eq!(env.step(Direction::Up).1, -0.75);
+    }
+
+    #[test]
+    fn running_out_of_steps_truncates() {
+        let mut env = env(EnvConfig {
+            max_steps: Some(2),
+            ..EnvConfig::default()
+        });
+        env.instance.food = GridCell { x: 0, y: 0 };
+        assert!(!env.step(Direction::Left).2);
+        let (_, _, done, info) = env.step(Direction::Left);
+        assert!(done && info.truncated && !info.won);
+    }
+
+    #[test]
+    fn filling_the_board_wins() {
+        // The snake fills all but the last cell of a single row, and wraps around into the food
+        let mut env = SnakeEnv::new(EnvConfig {
+            width: 6,
+            height: 1,
+            max_steps: Some(1),
+            ..EnvConfig::default()
+        });
+        env.reset(0);
+        assert_eq!(env.instance().food, GridCell { x: 5, y: 0 });
+        let (_, _, done, info) = env.step(Direction::Left);
+        assert!(done && info.won && !info.truncated);
+        assert_eq!(info.death, None);
+    }
+
+    #[test]
+    fn observations_have_their_shapes() {
+        let shapes = [
+            (ObservationKind::Grid, vec![GRID_CHANNELS, 10, 10]),
+            (
+                ObservationKind::EgoCentric { radius: 2 },
+                vec![EGO_CHANNELS, 5, 5],
+            ),
+            (ObservationKind::Features, vec![FEATURE_LEN]),
+        ];
+        for (observation, shape) in shapes {
+            let env = env(EnvConfig {
+                observation,
+                ..EnvConfig::default()
+            });
+            let observed = env.observe();
+            assert_eq!(observed.shape, shape);
+            assert_eq!(observed.data.len(), shape.iter().product::<usize>());
+        }
+    }
+
+    #[test]
+    fn ego_centric_view_faces_the_heading() {
+        let mut env = env(EnvConfig {
+            observation: ObservationKind::EgoCentric { radius: 2 },
+            ..EnvConfig::default()
+        });
+        let at = |observation: &Observation, channel: usize, row: usize, col: usize| {
+            observation.data[channel * 25 + row * 5 + col]
+        };
+        // Heading left, the food ahead is drawn above the head and the neck below it
+        env.instance.food = GridCell { x: 3, y: 4 };
+        let observation = env.observe();
+        assert_eq!(at(&observation, 1, 1, 2), 1.0);
+        assert_eq!(at(&observation, 0, 3, 2), 1.0);
+        // Food above the head on the board is on the snake's right
+        env.instance.food = GridCell { x: 4, y: 3 };
+        let observation = env.observe();
+        assert_eq!(at(&observation, 1, 2, 3), 1.0);
+        assert_eq!(observation.data[25..].iter().sum::<f32>(), 1.0);
+    }
+
+    #[test]
+    fn vec_env_steps_in_action_order() {
+        let config = EnvConfig {
+            width: 10,
+            height: 10,
+            ..EnvConfig::default()
+        };
+        let mut vec_env = VecEnv::new(config, 17);
+        vec_env.reset(100);
+        let actions: Vec<Direction> = (0..17)
+            .map(|i| Direction::ALL[i % Direction::ALL.len()])
+            .collect();
+        let results = vec_env.step(&actions);
+        assert_eq!(results.len(), 17);
+        for (i, (result, action)) in results.into_iter().zip(&actions).enumerate() {
+            let mut env = SnakeEnv::new(config);
+            env.reset(100 + i as u64);
+            assert_eq!(result, env.step(*action), "environment {i}");
+        }
+    }
+}
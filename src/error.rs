@@ -0,0 +1,42 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum GameError {
+    // The terminal cannot fit the game board or its messages
+    TerminalTooSmall {
+        size: (u16, u16),
+        needed: (u16, u16),
+    },
+    // Writing to the terminal failed, for example because it was closed
+    Output(io::Error),
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TerminalTooSmall { size, needed } => write!(
+                f,
+                "terminal is too small: it is {}x{}, but the game needs at least {}x{}",
+                size.0, size.1, needed.0, needed.1
+            ),
+            Self::Output(err) => write!(f, "failed to draw the game: {err}"),
+        }
+    }
+}
+
+impl Error for GameError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Output(err) => Some(err),
+            Self::TerminalTooSmall { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for GameError {
+    fn from(err: io::Error) -> Self {
+        Self::Output(err)
+    }
+}
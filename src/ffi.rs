@@ -0,0 +1,352 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::game_instance::{Direction, GameGrid, GameInstance, GridCell, TickOutcome};
+
+// Status codes returned by every function, mirrored in include/rs_snake.h
+pub const RS_SNAKE_OK: i32 = 0;
+pub const RS_SNAKE_NULL_POINTER: i32 = 1;
+pub const RS_SNAKE_INVALID_ARGUMENT: i32 = 2;
+pub const RS_SNAKE_GAME_OVER: i32 = 3;
+pub const RS_SNAKE_BUFFER_TOO_SMALL: i32 = 4;
+pub const RS_SNAKE_PANIC: i32 = 5;
+
+// Game states reported by `rs_snake_state`
+pub const RS_SNAKE_STATE_RUNNING: i32 = 0;
+pub const RS_SNAKE_STATE_OVER: i32 = 1;
+pub const RS_SNAKE_STATE_WON: i32 = 2;
+
+// Largest board `rs_snake_new` accepts, as width * height
+pub const RS_SNAKE_MAX_CELLS: u32 = GameGrid::MAX_CELLS;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RsSnakeCell {
+    pub x: u16,
+    pub y: u16,
+}
+
+impl From<&GridCell> for RsSnakeCell {
+    fn from(cell: &GridCell) -> Self {
+        Self {
+            x: cell.x,
+            y: cell.y,
+        }
+    }
+}
+
+// Opaque handle handed out to C callers
+pub struct RsSnakeGame {
+    instance: GameInstance,
+    state: i32,
+}
+
+// Run `f`, turning any panic into a status code so it never unwinds into C
+fn guard(f: impl FnOnce() -> i32) -> i32 {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(RS_SNAKE_PANIC)
+}
+
+fn direction_from_raw(direction: i32) -> Option<Direction> {
+    match direction {
+        0 => Some(Direction::Up),
+        1 => Some(Direction::Down),
+        2 => Some(Direction::Left),
+        3 => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+/// # Safety
+/// `out` must be a valid pointer to writable storage for one handle.
+#[no_mangle]
+pub unsafe extern "C" fn rs_snake_new(
+    width: u16,
+    height: u16,
+    seed: u64,
+    out: *mut *mut RsSnakeGame,
+) -> i32 {
+    guard(|| {
+        if out.is_null() {
+            return RS_SNAKE_NULL_POINTER;
+        }
+        if !GameGrid::fits(width, height) {
+            return RS_SNAKE_INVALID_ARGUMENT;
+        }
+        let grid = GameGrid::with_dimensions(width, height);
+        let game = Box::new(RsSnakeGame {
+            instance: GameInstance::with_seed(grid, seed),
+            state: RS_SNAKE_STATE_RUNNING,
+        });
+        *out = Box::into_raw(game);
+        RS_SNAKE_OK
+    })
+}
+
+/// # Safety
+/// `game` must be null or a handle returned by `rs_snake_new` that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rs_snake_free(game: *mut RsSnakeGame) {
+    if !game.is_null() {
+        drop(Box::from_raw(game));
+    }
+}
+
+/// # Safety
+/// `game` must be a live handle, `state_out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rs_snake_step(
+    game: *mut RsSnakeGame,
+    direction: i32,
+    state_out: *mut i32,
+) -> i32 {
+    guard(|| {
+        let Some(game) = game.as_mut() else {
+            return RS_SNAKE_NULL_POINTER;
+        };
+        let Some(direction) = direction_from_raw(direction) else {
+            return RS_SNAKE_INVALID_ARGUMENT;
+        };
+        if game.state != RS_SNAKE_STATE_RUNNING {
+            return RS_SNAKE_GAME_OVER;
+        }
+        game.instance.turn(0, direction);
+        let tick = game.instance.game_cycle();
+        game.state = match tick.outcomes[0] {
+            Some(TickOutcome::Died(_)) => RS_SNAKE_STATE_OVER,
+            _ if tick.board_full => RS_SNAKE_STATE_WON,
+            _ => RS_SNAKE_STATE_RUNNING,
+        };
+        if !state_out.is_null() {
+            *state_out = game.state;
+        }
+        RS_SNAKE_OK
+    })
+}
+
+/// # Safety
+/// `game` must be a live handle, `state_out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rs_snake_state(game: *const RsSnakeGame, state_out: *mut i32) -> i32 {
+    guard(|| {
+        let Some(game) = game.as_ref() else {
+            return RS_SNAKE_NULL_POINTER;
+        };
+        if state_out.is_null() {
+            return RS_SNAKE_NULL_POINTER;
+        }
+        *state_out = game.state;
+        RS_SNAKE_OK
+    })
+}
+
+/// # Safety
+/// `game` must be a live handle, `food_out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rs_snake_food(
+    game: *const RsSnakeGame,
+    food_out: *mut RsSnakeCell,
+) -> i32 {
+    guard(|| {
+        let Some(game) = game.as_ref() else {
+            return RS_SNAKE_NULL_POINTER;
+        };
+        if food_out.is_null() {
+            return RS_SNAKE_NULL_POINTER;
+        }
+        *food_out = RsSnakeCell::from(&game.instance.food);
+        RS_SNAKE_OK
+    })
+}
+
+/// # Safety
+/// `game` must be a live handle, `cells` must be null or valid for `capacity` writes,
+/// `len_out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn rs_snake_cells(
+    game: *const RsSnakeGame,
+    cells: *mut RsSnakeCell,
+    capacity: usize,
+    len_out: *mut usize,
+) -> i32 {
+    guard(|| {
+        let Some(game) = game.as_ref() else {
+            return RS_SNAKE_NULL_POINTER;
+        };
+        if len_out.is_null() {
+            return RS_SNAKE_NULL_POINTER;
+        }
+        // Always report the required length so callers can size their buffer
+        let body = &game.instance.snakes[0].body;
+        *len_out = body.len();
+        if body.len() > capacity {
+            return RS_SNAKE_BUFFER_TOO_SMALL;
+        }
+        if cells.is_null() {
+            return RS_SNAKE_NULL_POINTER;
+        }
+        for (i, segment) in body.iter().enumerate() {
+            ptr::write(cells.add(i), RsSnakeCell::from(segment));
+        }
+        RS_SNAKE_OK
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UP: i32 = 0;
+    const DOWN: i32 = 1;
+    const LEFT: i32 = 2;
+    const RIGHT: i32 = 3;
+
+    fn new_game(width: u16, height: u16) -> *mut RsSnakeGame {
+        let mut game = ptr::null_mut();
+        assert_eq!(
+            unsafe { rs_snake_new(width, height, 7, &mut game) },
+            RS_SNAKE_OK
+        );
+        assert!(!game.is_null());
+        game
+    }
+
+    fn cells(game: *const RsSnakeGame) -> Vec<(u16, u16)> {
+        let mut len = 0;
+        let status = unsafe { rs_snake_cells(game, ptr::null_mut(), 0, &mut len) };
+        assert_eq!(status, RS_SNAKE_BUFFER_TOO_SMALL);
+        let mut buffer = vec![RsSnakeCell { x: 0, y: 0 }; len];
+        let status = unsafe { rs_snake_cells(game, buffer.as_mut_ptr(), len, &mut len) };
+        assert_eq!(status, RS_SNAKE_OK);
+        buffer.iter().map(|cell| (cell.x, cell.y)).collect()
+    }
+
+    #[test]
+    fn a_step_moves_the_snake_and_reports_the_state() {
+        let game = new_game(10, 10);
+        let before = cells(game);
+        assert_eq!(before.len(), 5);
+
+        let mut state = -1;
+        assert_eq!(unsafe { rs_snake_step(game, UP, &mut state) }, RS_SNAKE_OK);
+        assert_eq!(state, RS_SNAKE_STATE_RUNNING);
+        let after = cells(game);
+        assert_eq!(after[0], (before[0].0, before[0].1 - 1));
+        assert_eq!(after[1..], before[..before.len() - 1]);
+
+        let mut food = RsSnakeCell {
+            x: u16::MAX,
+            y: u16::MAX,
+        };
+        assert_eq!(unsafe { rs_snake_food(game, &mut food) }, RS_SNAKE_OK);
+        assert!(food.x < 10 && food.y < 10);
+        assert!(!after.contains(&(food.x, food.y)));
+        unsafe { rs_snake_free(game) };
+    }
+
+    #[test]
+    fn running_into_its_own_body_ends_the_game() {
+        let game = new_game(10, 10);
+        let mut state = -1;
+        for direction in [UP, LEFT, DOWN, RIGHT] {
+            assert_eq!(
+                unsafe { rs_snake_step(game, direction, &mut state) },
+                RS_SNAKE_OK
+            );
+        }
+        assert_eq!(state, RS_SNAKE_STATE_OVER);
+        assert_eq!(
+            unsafe { rs_snake_step(game, LEFT, ptr::null_mut()) },
+            RS_SNAKE_GAME_OVER
+        );
+        assert_eq!(unsafe { rs_snake_state(game, &mut state) }, RS_SNAKE_OK);
+        assert_eq!(state, RS_SNAKE_STATE_OVER);
+        unsafe { rs_snake_free(game) };
+    }
+
+    #[test]
+    fn filling_the_board_wins() {
+        // The snake covers five of the six cells, so the food is on the one left over
+        let game = new_game(GameGrid::MIN_WIDTH, GameGrid::MIN_HEIGHT);
+        let mut state = -1;
+        assert_eq!(
+            unsafe { rs_snake_step(game, LEFT, &mut state) },
+            RS_SNAKE_OK
+        );
+        assert_eq!(state, RS_SNAKE_STATE_WON);
+        assert_eq!(cells(game).len(), 6);
+        assert_eq!(
+            unsafe { rs_snake_step(game, LEFT, &mut state) },
+            RS_SNAKE_GAME_OVER
+        );
+        unsafe { rs_snake_free(game) };
+    }
+
+    #[test]
+    fn sizes_outside_the_limits_are_rejected() {
+        let mut game = ptr::null_mut();
+        for (width, height) in [
+            (GameGrid::MIN_WIDTH - 1, 10),
+            (10, 0),
+            (u16::MAX, u16::MAX),
+            (1025, 1024),
+        ] {
+            let status = unsafe { rs_snake_new(width, height, 0, &mut game) };
+            assert_eq!(status, RS_SNAKE_INVALID_ARGUMENT, "{width}x{height}");
+            assert!(game.is_null());
+        }
+        assert_eq!(RS_SNAKE_MAX_CELLS, 1024 * 1024);
+        let game = new_game(1024, 1024);
+        unsafe { rs_snake_free(game) };
+    }
+
+    #[test]
+    fn null_pointers_and_bad_directions_return_error_codes() {
+        let game = new_game(10, 10);
+        let mut state = 0;
+        let mut food = RsSnakeCell { x: 0, y: 0 };
+        let mut len = 0;
+        unsafe {
+            assert_eq!(
+                rs_snake_new(10, 10, 0, ptr::null_mut()),
+                RS_SNAKE_NULL_POINTER
+            );
+            assert_eq!(
+                rs_snake_step(ptr::null_mut(), UP, &mut state),
+                RS_SNAKE_NULL_POINTER
+            );
+            assert_eq!(
+                rs_snake_state(ptr::null(), &mut state),
+                RS_SNAKE_NULL_POINTER
+            );
+            assert_eq!(rs_snake_state(game, ptr::null_mut()), RS_SNAKE_NULL_POINTER);
+            assert_eq!(rs_snake_food(ptr::null(), &mut food), RS_SNAKE_NULL_POINTER);
+            assert_eq!(rs_snake_food(game, ptr::null_mut()), RS_SNAKE_NULL_POINTER);
+            assert_eq!(
+                rs_snake_cells(ptr::null(), ptr::null_mut(), 0, &mut len),
+                RS_SNAKE_NULL_POINTER
+            );
+            assert_eq!(
+                rs_snake_cells(game, ptr::null_mut(), 0, ptr::null_mut()),
+                RS_SNAKE_NULL_POINTER
+            );
+            assert_eq!(
+                rs_snake_cells(game, ptr::null_mut(), 64, &mut len),
+                RS_SNAKE_NULL_POINTER
+            );
+
+            for direction in [-1, 4, i32::MAX] {
+                assert_eq!(
+                    rs_snake_step(game, direction, &mut state),
+                    RS_SNAKE_INVALID_ARGUMENT
+                );
+            }
+            // Rejected calls leave the game untouched
+            assert_eq!(rs_snake_state(game, &mut state), RS_SNAKE_OK);
+            assert_eq!(state, RS_SNAKE_STATE_RUNNING);
+            assert_eq!(rs_snake_step(game, DOWN, &mut state), RS_SNAKE_OK);
+
+            rs_snake_free(game);
+            rs_snake_free(ptr::null_mut());
+        }
+    }
+}
@@ -1,30 +1,121 @@
-use std::{thread, time::Duration};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::ai;
+use crate::error::GameError;
 use crate::game_input::{self, KeyPress};
-use crate::game_instance::{Direction, GameInstance};
-use crate::game_output;
-use crate::parser::{ArgsParser, GridSize, MovementKeyScheme, Speed};
+use crate::game_instance::{DeathCause, Direction, GameGrid, GameInstance};
+use crate::game_output::{self, CellLayout};
+use crate::highscores::{HighScores, Score};
+use crate::net_protocol::ServerMessage;
+use crate::parser::{
+    cycle_value, value_name, ArgsParser, Difficulty, GameMode, GridSize, RenderMode, Speed,
+    WinCondition,
+};
+use crate::settings::Settings;
+use crate::signals::{AspectSource, SizeSource, Suspend};
+use crate::spectator::SpectatorServer;
+use crate::ticker::{Ticker, INPUT_POLL};
+
+// Seconds counted down before the snakes start moving
+const COUNTDOWN_FROM: u32 = 3;
+const COUNTDOWN_STEP: Duration = Duration::from_millis(700);
+// Height of a terminal cell divided by its width, when the terminal does not report it
+const DEFAULT_CELL_ASPECT: f64 = 2.0;
+// Smallest terminal the menus and messages fit on
+const MIN_TERMINAL_SIZE: (u16, u16) = (40, 20);
+// Smallest board a round is played on. It also needs a row for every snake, plus one.
+const MIN_GRID_WIDTH: u16 = 10;
 
+#[derive(Debug)]
 struct Options {
     grid_size: GridSize,
     speed: Speed,
-    movement_key_scheme: MovementKeyScheme,
+    mode: GameMode,
+    versus: VersusOptions,
+    render_mode: RenderMode,
+}
+
+#[derive(Debug)]
+struct VersusOptions {
+    opponents: usize,
+    difficulty: Difficulty,
+    win_condition: WinCondition,
+    time_limit: Duration,
 }
 
 impl Options {
-    fn new(grid_size: GridSize, speed: Speed, movement_key_scheme: MovementKeyScheme) -> Self {
+    fn new(
+        grid_size: GridSize,
+        speed: Speed,
+        mode: GameMode,
+        versus: VersusOptions,
+        render_mode: RenderMode,
+    ) -> Self {
         Self {
             grid_size,
             speed,
-            movement_key_scheme,
+            mode,
+            versus,
+            render_mode,
         }
     }
     fn from_args(args: ArgsParser) -> Self {
-        Options::new(args.grid_size, args.speed, args.movement_key_scheme)
+        let versus = VersusOptions {
+            opponents: args.opponents as usize,
+            difficulty: args.difficulty,
+            win_condition: args.win_condition,
+            time_limit: Duration::from_secs(args.time_limit),
+        };
+        Options::new(
+            args.grid_size,
+            args.speed,
+            args.mode,
+            versus,
+            args.render_mode,
+        )
     }
-    fn new_pre_game() -> Self {
-        Options::new(GridSize::Large, Speed::High, MovementKeyScheme::Arrows)
+
+    fn layout(&self) -> CellLayout {
+        match self.render_mode {
+            RenderMode::Normal => CellLayout::single(1),
+            RenderMode::Square => CellLayout::single(2),
+            RenderMode::HalfBlock => CellLayout::half_block(),
+            RenderMode::Braille => CellLayout::braille(),
+        }
+    }
+}
+
+// Smallest terminal a round with these options can be played on
+fn needed_terminal_size(options: &Options) -> (u16, u16) {
+    let percent = options.grid_size.value();
+    let snakes = match options.mode {
+        GameMode::SinglePlayer => 1,
+        GameMode::TwoPlayer => 2,
+        GameMode::Versus => options.versus.opponents as u16 + 1,
+    };
+    let (min_x, min_y) = MIN_TERMINAL_SIZE;
+    let layout = options.layout();
+    let grid_size = |x, y| GameGrid::size_for(&TerminalSize::new(layout.cells_in(x, y)), percent);
+    let x = (min_x..)
+        .find(|&x| grid_size(x, min_y).0 >= MIN_GRID_WIDTH)
+        .unwrap();
+    let y = (min_y..)
+        .find(|&y| grid_size(min_x, y).1 >= snakes + 2)
+        .unwrap();
+    (x, y)
+}
+
+// Check that the game can be played on a terminal of `size`, before taking it over
+pub fn check_terminal_size(args: &ArgsParser, size: (u16, u16)) -> Result<(), GameError> {
+    let needed = needed_terminal_size(&Options::from_args(args.clone()));
+    if size.0 < needed.0 || size.1 < needed.1 {
+        return Err(GameError::TerminalTooSmall { size, needed });
     }
+    Ok(())
 }
 
 pub struct TerminalSize {
@@ -41,6 +132,83 @@ impl TerminalSize {
     pub fn y(&self) -> u16 {
         self.xy.1
     }
+    // The same area measured in grid cells laid out by `layout`
+    fn in_cells(&self, layout: CellLayout) -> Self {
+        Self::new(layout.cells_in(self.x(), self.y()))
+    }
+}
+
+// Entries of the main menu, the pause menu and their settings pages
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum MenuEntry {
+    Play,
+    Resume,
+    Restart,
+    Mode,
+    // Speed of the snakes
+    Level,
+    Settings,
+    HighScores,
+    BoardSize,
+    Keys,
+    Difficulty,
+    Opponents,
+    Skin,
+    Back,
+    Quit,
+}
+
+impl MenuEntry {
+    // Entries whose value is changed with the left and right keys
+    fn is_setting(&self) -> bool {
+        matches!(
+            self,
+            Self::Mode
+                | Self::Level
+                | Self::BoardSize
+                | Self::Keys
+                | Self::Difficulty
+                | Self::Opponents
+                | Self::Skin
+        )
+    }
+}
+
+const MAIN_MENU: [MenuEntry; 6] = [
+    MenuEntry::Play,
+    MenuEntry::Mode,
+    MenuEntry::Level,
+    MenuEntry::Settings,
+    MenuEntry::HighScores,
+    MenuEntry::Quit,
+];
+const MAIN_SETTINGS: [MenuEntry; 6] = [
+    MenuEntry::BoardSize,
+    MenuEntry::Keys,
+    MenuEntry::Difficulty,
+    MenuEntry::Opponents,
+    MenuEntry::Skin,
+    MenuEntry::Back,
+];
+const PAUSE_MENU: [MenuEntry; 4] = [
+    MenuEntry::Resume,
+    MenuEntry::Restart,
+    MenuEntry::Settings,
+    MenuEntry::Quit,
+];
+// Settings that can change in the middle of a round
+const PAUSE_SETTINGS: [MenuEntry; 4] = [
+    MenuEntry::Level,
+    MenuEntry::Keys,
+    MenuEntry::Skin,
+    MenuEntry::Back,
+];
+
+// What a key press did to a menu
+enum MenuAction {
+    Pick(MenuEntry),
+    Redraw,
+    Nothing,
 }
 
 #[derive(Debug, PartialEq)]
@@ -51,53 +219,164 @@ enum GameState {
     QuitButtonPressed,
     RestartGame,
     GameOverTransition,
+    // The snake filled the whole board
+    Victory,
 }
 
 pub struct Game {
+    // Options as given on the command line and changed in the menus since
+    args: ArgsParser,
     options: Options,
     state: GameState,
     instance: GameInstance,
+    // Choices of the computer-controlled snakes, seeded from the round so its seed replays them
+    opponent_rng: StdRng,
     input: game_input::GameInput,
     output: game_output::GameOutput,
     terminal_size: TerminalSize,
+    // Height of a terminal cell divided by its width
+    cell_aspect: f64,
+    ticker: Ticker,
+    // Time the current or last round was played, leaving out pauses, countdowns and
+    // suspensions, and when play last went on while it is running
+    round_time: Duration,
+    running_since: Option<Instant>,
+    // Place of the last finished round in the high scores
+    round_rank: Option<usize>,
+    // Ticks the last finished round dropped because the game fell behind
+    round_dropped: u64,
+    high_scores: Option<HighScores>,
+    settings: Option<Settings>,
+    spectators: Option<SpectatorServer>,
+    resize: Option<SizeSource>,
+    aspect: Option<AspectSource>,
+    suspend: Option<Suspend>,
 }
 
 impl Game {
     pub fn new(
         args: ArgsParser,
         input: game_input::GameInput,
-        output: game_output::GameOutput,
+        mut output: game_output::GameOutput,
         terminal_size: (u16, u16),
     ) -> Game {
         let terminal_size = TerminalSize::new(terminal_size);
+        let cell_aspect = args.cell_aspect.unwrap_or(DEFAULT_CELL_ASPECT);
         let state = GameState::PreGame;
-        let options = Options::from_args(args);
-        let instance = GameInstance::new(&terminal_size, options.grid_size.value());
+        let options = Options::from_args(args.clone());
+        let ticker = Ticker::new(Duration::from_millis(options.speed.value()));
+        let instance = Self::new_instance(&options, &terminal_size);
+        let opponent_rng = StdRng::seed_from_u64(instance.seed);
+        output.set_layout(options.layout());
+        output.set_skin(args.skin);
         Game {
+            args,
             options,
             state,
             instance,
+            opponent_rng,
             input,
             output,
             terminal_size,
+            cell_aspect,
+            ticker,
+            round_time: Duration::ZERO,
+            running_since: None,
+            round_rank: None,
+            round_dropped: 0,
+            high_scores: None,
+            settings: None,
+            spectators: None,
+            resize: None,
+            aspect: None,
+            suspend: None,
+        }
+    }
+
+    // Stream every round to the watchers connecting to `spectators`
+    pub fn set_spectators(&mut self, spectators: SpectatorServer) {
+        self.spectators = Some(spectators);
+    }
+
+    // Enter the results of single-player rounds into `high_scores`
+    pub fn set_high_scores(&mut self, high_scores: HighScores) {
+        self.high_scores = Some(high_scores);
+    }
+
+    // Save the choices made in the menus to `settings`
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = Some(settings);
+    }
+
+    // Measure the aspect of the terminal's cells with `aspect` now and whenever the terminal
+    // changes, since a new font or window can change it
+    pub fn set_aspect_source(&mut self, aspect: AspectSource) {
+        self.aspect = Some(aspect);
+        self.measure_cell_aspect();
+    }
+
+    // Follow size changes of the terminal reported by `resize`
+    pub fn set_size_source(&mut self, resize: SizeSource) {
+        self.resize = Some(resize);
+    }
+
+    // Let Ctrl-Z hand the terminal back to the shell through `suspend`
+    pub fn set_suspend(&mut self, suspend: Suspend) {
+        self.suspend = Some(suspend);
+    }
+
+    // Everything needed to understand or replay the current round, for crash reports
+    pub fn crash_report(&self) -> String {
+        let instance = &self.instance;
+        let (x_min, y_min, x_max, y_max) = instance.grid.get_corners();
+        let mut report = format!(
+            "options: {:?}\nstate: {:?}\nterminal size: {}x{}\nseed: {}\ntick: {}\n\
+             dropped ticks: {}\nround time: {:.1}s\ngrid: ({x_min}, {y_min}) to ({x_max}, {y_max})\n\
+             food: ({}, {})\n",
+            self.options,
+            self.state,
+            self.terminal_size.x(),
+            self.terminal_size.y(),
+            instance.seed,
+            instance.ticks,
+            self.ticker.dropped(),
+            self.played().as_secs_f64(),
+            instance.food.x,
+            instance.food.y,
+        );
+        for (player, snake) in instance.snakes.iter().enumerate() {
+            let body: Vec<String> = snake
+                .body
+                .iter()
+                .map(|cell| format!("({}, {})", cell.x, cell.y))
+                .collect();
+            report += &format!(
+                "snake {player}: alive {}, death {:?}, direction {:?}, food eaten {}, body {}\n",
+                snake.alive,
+                snake.death,
+                snake.direction,
+                snake.food_eaten,
+                body.join(" ")
+            );
         }
+        report
     }
 
     // return value = new state? or middleman function interprets return value and gives new state?
-    pub fn run(&mut self) {
+    pub fn run(&mut self) -> Result<(), GameError> {
         loop {
             match self.state {
                 GameState::PreGame => {
-                    self.state = self.pre_game();
+                    self.state = self.pre_game()?;
                 }
                 GameState::InProgress => {
-                    self.state = self.in_progress_game();
+                    self.state = self.in_progress_game()?;
                 }
                 GameState::GameOverTransition => {
-                    self.state = self.game_over_transition();
+                    self.state = self.game_over_transition()?;
                 }
-                GameState::GameOver => {
-                    self.state = self.game_over();
+                GameState::GameOver | GameState::Victory => {
+                    self.state = self.game_over()?;
                 }
                 GameState::RestartGame => {
                     self.state = self.restart_game();
@@ -107,140 +386,945 @@ impl Game {
                 }
             }
         }
-        self.output.show_cursor();
+        self.output.show_cursor()?;
+        self.output.render()
     }
 
-    fn pre_game(&mut self) -> GameState {
-        self.instance = GameInstance::new_pre_game(&self.terminal_size);
-        self.output.clear_screen();
-        self.output.draw_pre_game_message();
-        self.output.draw_snake(&self.instance.snake);
-        self.output.render();
+    // Show the main menu over a snake running along the bottom of the screen
+    fn pre_game(&mut self) -> Result<GameState, GameError> {
+        let mut menu: &[MenuEntry] = &MAIN_MENU;
+        let mut selected = 0;
+        self.ticker = Ticker::new(Duration::from_millis(self.options.speed.value()));
+        if !self.draw_pre_game(menu, selected)? {
+            return Ok(GameState::QuitButtonPressed);
+        }
         loop {
-            match self.input.get_keypress() {
+            let keypress = self.input.get_keypress();
+            match (keypress, self.navigate_menu(keypress, menu, &mut selected)) {
                 // Start playing the game
-                KeyPress::Pause => break,
+                (KeyPress::Pause, _) | (_, MenuAction::Pick(MenuEntry::Play)) => break,
                 // Quit the game
-                KeyPress::Quit => return GameState::QuitButtonPressed,
-                _ => (),
+                (KeyPress::Quit, _) | (_, MenuAction::Pick(MenuEntry::Quit)) => {
+                    return Ok(GameState::QuitButtonPressed)
+                }
+                (_, MenuAction::Pick(MenuEntry::Settings)) => {
+                    (menu, selected) = (&MAIN_SETTINGS, 0)
+                }
+                (_, MenuAction::Pick(MenuEntry::Back)) => (menu, selected) = (&MAIN_MENU, 3),
+                (_, MenuAction::Pick(MenuEntry::HighScores)) => {
+                    let scores = self.high_score_lines();
+                    self.show_info("scores", &scores)?;
+                }
+                (_, MenuAction::Pick(_) | MenuAction::Redraw) => (),
+                // Stop the program, drawing the menu again once it continues
+                (KeyPress::Suspend, MenuAction::Nothing) => {
+                    if self.suspend_terminal()? && !self.draw_pre_game(menu, selected)? {
+                        return Ok(GameState::QuitButtonPressed);
+                    }
+                }
+                (_, MenuAction::Nothing) => {
+                    if self.poll_resize() && !self.draw_pre_game(menu, selected)? {
+                        return Ok(GameState::QuitButtonPressed);
+                    }
+                    if self.ticker.tick() {
+                        self.instance.game_cycle();
+                        self.draw_snakes()?;
+                        self.output.render()?;
+                        self.accept_spectators(false);
+                    }
+                    self.ticker.sleep(INPUT_POLL);
+                    continue;
+                }
             }
-            self.instance.game_cycle();
-            self.output.draw_snake(&self.instance.snake);
-            self.output.render();
-
-            // Sleep here to let input thread have some control
-            thread::sleep(Duration::from_millis(self.options.speed.value()));
+            self.draw_main_menu(menu, selected)?;
         }
-        GameState::InProgress
+        Ok(GameState::InProgress)
     }
 
-    fn in_progress_game(&mut self) -> GameState {
-        self.instance = GameInstance::new(&self.terminal_size, self.options.grid_size.value());
+    fn in_progress_game(&mut self) -> Result<GameState, GameError> {
+        // The terminal may have shrunk since the last round
+        if !self.wait_for_room()? {
+            return Ok(GameState::QuitButtonPressed);
+        }
+        self.instance = Self::new_instance(&self.options, &self.terminal_size);
+        self.opponent_rng = StdRng::seed_from_u64(self.instance.seed);
         // Initial render
-        self.output.clear_screen();
-        self.draw_border();
-        self.output.draw_snake(&self.instance.snake);
-        self.output.draw_food(&self.instance.food);
-        self.output.render();
+        self.output.clear_screen()?;
+        self.draw_all()?;
+        self.output.render()?;
+        self.broadcast_to_spectators(&ServerMessage::spectate(&self.instance));
+        self.broadcast_to_spectators(&ServerMessage::snapshot(&self.instance));
+        if !self.countdown()? {
+            return Ok(GameState::QuitButtonPressed);
+        }
+        self.round_time = Duration::ZERO;
+        self.start_clock();
+        self.ticker = Ticker::new(self.tick_interval());
 
+        // Directions pressed since the last tick, keeping only the latest of every player
+        let mut turns = vec![None; self.instance.snakes.len()];
         // Start of main loop
         'mainloop: loop {
+            // Pause the game whenever the terminal is resized
+            if self.poll_resize() {
+                self.stop_clock();
+                if !self.fit_board()? {
+                    return Ok(GameState::QuitButtonPressed);
+                }
+                if let Some(state) = self.pause()? {
+                    return Ok(state);
+                }
+                self.ticker.restart();
+                self.start_clock();
+            }
             // Handle user input
-            match self.input.get_keypress() {
-                // Pause the game
-                KeyPress::Pause => {
-                    while let KeyPress::None | KeyPress::Other = self.input.get_keypress() {
-                        thread::sleep(Duration::from_millis(10));
+            for keypress in self.input.get_keypresses() {
+                match keypress {
+                    // Pause the game, which may also restart or end it
+                    KeyPress::Pause => {
+                        self.stop_clock();
+                        if let Some(state) = self.pause()? {
+                            return Ok(state);
+                        }
+                        self.ticker.restart();
+                        self.start_clock();
                     }
+                    // Quit the game
+                    KeyPress::Quit => return Ok(GameState::QuitButtonPressed),
+                    // Stop the program, pausing the round until the player is back
+                    KeyPress::Suspend => {
+                        self.stop_clock();
+                        if !self.suspend_round()? {
+                            return Ok(GameState::QuitButtonPressed);
+                        }
+                        self.ticker.restart();
+                        self.start_clock();
+                    }
+                    // Get pressed direction key
+                    KeyPress::DirectionKey(player, direction) if player < turns.len() => {
+                        turns[player] = Some(direction);
+                    }
+                    _ => (),
                 }
-                // Quit the game
-                KeyPress::Quit => return GameState::QuitButtonPressed,
-                // Get pressed direction key
-                KeyPress::DirectionKey(Direction::Up) if !self.instance.direction.vertical() => {
-                    self.instance.direction = Direction::Up;
-                }
-                KeyPress::DirectionKey(Direction::Down) if !self.instance.direction.vertical() => {
-                    self.instance.direction = Direction::Down;
+            }
+            if !self.ticker.tick() {
+                self.ticker.sleep(INPUT_POLL);
+                continue;
+            }
+            for (player, direction) in turns.iter_mut().enumerate() {
+                if let Some(direction) = direction.take() {
+                    self.instance.turn(player, direction);
                 }
-                KeyPress::DirectionKey(Direction::Left) if self.instance.direction.vertical() => {
-                    self.instance.direction = Direction::Left;
+            }
+            self.steer_opponents();
+
+            let alive: Vec<bool> = self.instance.snakes.iter().map(|s| s.alive).collect();
+            let tick = self.instance.game_cycle();
+            self.broadcast_to_spectators(&ServerMessage::tick(&self.instance, &alive));
+            // Watchers joining now start from a snapshot that already includes this tick
+            self.accept_spectators(true);
+            // Remove snakes that died this tick from the board, like watchers do
+            for (player, was_alive) in alive.into_iter().enumerate() {
+                if was_alive && !self.snake_visible(player) {
+                    self.output.undraw_snake(&self.instance.snakes[player])?;
                 }
-                KeyPress::DirectionKey(Direction::Right) if self.instance.direction.vertical() => {
-                    self.instance.direction = Direction::Right;
+            }
+
+            let board_full = tick.board_full;
+            if board_full || self.round_over() {
+                let winner = self.winner();
+                self.broadcast_to_spectators(&ServerMessage::Over { winner });
+                self.finish_round(board_full);
+                if board_full && self.options.mode == GameMode::SinglePlayer {
+                    // The food is left under the snake, so only the snake is drawn
+                    self.draw_snakes()?;
+                    self.output.render()?;
+                    thread::sleep(Duration::from_millis(1000));
+                    return Ok(GameState::Victory);
                 }
-                _ => (),
+                break 'mainloop;
             }
+            self.draw_snakes()?;
+            self.output.draw_food(&self.instance.food)?;
+            self.output.render()?;
+            self.ticker.set_interval(self.tick_interval());
+        }
+        Ok(GameState::GameOverTransition)
+    }
 
-            let proceed = self.instance.game_cycle();
+    // Cells are usually taller than wide, so a vertical move covers more of the screen. Ticks
+    // are stretched by the cells' aspect ratio while every snake is moving vertically, so the
+    // snakes appear to move equally fast in all directions. Render modes with about square
+    // cells make up for most of the difference themselves. All snakes move on the same tick, so
+    // with several snakes the correction only applies while none of them moves horizontally.
+    fn tick_interval(&self) -> Duration {
+        let vertical = self
+            .instance
+            .snakes
+            .iter()
+            .all(|snake| snake.direction.vertical());
+        let scale = if vertical {
+            self.options.layout().aspect(self.cell_aspect)
+        } else {
+            1.0
+        };
+        Duration::from_secs_f64(self.options.speed.value() as f64 * scale / 1000.0)
+    }
 
-            if !proceed {
-                break 'mainloop;
+    fn game_over(&mut self) -> Result<GameState, GameError> {
+        if !self.draw_game_over()? {
+            return Ok(GameState::QuitButtonPressed);
+        }
+
+        // Handle input
+        self.input.empty_key_buffer();
+        loop {
+            let keypress = self.input.wait_for_keypress(Some(INPUT_POLL));
+            match keypress {
+                KeyPress::Pause => return Ok(GameState::RestartGame),
+                KeyPress::Quit => return Ok(GameState::QuitButtonPressed),
+                KeyPress::Suspend if self.suspend_terminal()? && !self.draw_game_over()? => {
+                    return Ok(GameState::QuitButtonPressed)
+                }
+                _ => (),
             }
-            self.output.draw_snake(&self.instance.snake);
-            self.output.draw_food(&self.instance.food);
-            self.output.render();
-            thread::sleep(Duration::from_millis(
-                if self.instance.direction.vertical() {
-                    self.options.speed.value() + 20
-                } else {
-                    self.options.speed.value()
-                },
-            ));
+            if self.poll_resize() && !self.draw_game_over()? {
+                return Ok(GameState::QuitButtonPressed);
+            }
+            self.accept_spectators(false);
         }
-        GameState::GameOverTransition
     }
 
-    fn game_over(&mut self) -> GameState {
+    // Returns false if the player quits while the terminal is too small
+    fn draw_game_over(&mut self) -> Result<bool, GameError> {
+        if !self.wait_for_room()? {
+            return Ok(false);
+        }
         // Clear terminal
-        self.output.clear_screen();
+        self.output.clear_screen()?;
 
         // Render game over screen
+        match self.options.mode {
+            GameMode::SinglePlayer if self.state == GameState::Victory => {
+                let stats = self.victory_stats();
+                self.output.draw_victory_message(&stats)?;
+            }
+            GameMode::SinglePlayer => {
+                let mut notes: Vec<String> = self.death_report(0).into_iter().collect();
+                notes.extend(self.dropped_ticks());
+                self.output
+                    .draw_game_over_message(self.instance.snakes[0].body.len(), &notes)?;
+            }
+            GameMode::TwoPlayer | GameMode::Versus => {
+                let (title, results) = self.round_results();
+                self.output.draw_winner_message(&title, &results)?;
+            }
+        }
+        self.output.render()?;
+        Ok(true)
+    }
+
+    // Start the snake running below the main menu. Returns false if the player quits while the
+    // terminal is too small.
+    fn draw_pre_game(&mut self, menu: &[MenuEntry], selected: usize) -> Result<bool, GameError> {
+        if !self.wait_for_room()? {
+            return Ok(false);
+        }
+        self.instance =
+            GameInstance::new_pre_game(&self.terminal_size.in_cells(self.options.layout()));
+        self.draw_main_menu(menu, selected)?;
+        Ok(true)
+    }
+
+    fn draw_main_menu(&mut self, menu: &[MenuEntry], selected: usize) -> Result<(), GameError> {
+        self.output.clear_screen()?;
+        // Centre the menu in the space above the snake
+        let snake_row = self
+            .output
+            .layout()
+            .row(self.instance.snakes[0].get_head().y);
+        self.draw_menu(
+            self.terminal_size.x() / 2 + 1,
+            snake_row / 2,
+            menu,
+            selected,
+        )?;
+        self.draw_snakes()?;
+        self.output.render()
+    }
+
+    // Show `lines` in a box until a key is pressed, then draw the main menu again
+    fn show_info(&mut self, title: &str, lines: &[String]) -> Result<(), GameError> {
+        let mut entries = lines.to_vec();
+        entries.push(String::from("Back"));
+        let snake_row = self
+            .output
+            .layout()
+            .row(self.instance.snakes[0].get_head().y);
+        let center = (self.terminal_size.x() / 2 + 1, snake_row / 2);
+        self.output.clear_screen()?;
         self.output
-            .draw_game_over_message(self.instance.snake.body.len());
-        self.output.render();
+            .draw_menu(center.0, center.1, title, &entries, entries.len() - 1)?;
+        self.output.render()?;
+        loop {
+            match self.input.wait_for_keypress(Some(INPUT_POLL)) {
+                KeyPress::None | KeyPress::Other | KeyPress::DirectionKey(..) => (),
+                _ => return Ok(()),
+            }
+            // Go back to the menu, which fits itself to the new size
+            if self.poll_resize() {
+                return Ok(());
+            }
+        }
+    }
 
-        // Handle input
-        self.input.empty_key_buffer();
+    // One line per high score, best first
+    fn high_score_lines(&self) -> Vec<String> {
+        let scores = self
+            .high_scores
+            .as_ref()
+            .map_or(&[][..], HighScores::scores);
+        if scores.is_empty() {
+            return vec![String::from("No high scores yet")];
+        }
+        let mut lines: Vec<String> = scores
+            .iter()
+            .enumerate()
+            .map(|(rank, score)| {
+                format!(
+                    "{:>2}. length {:<4} {:>5}s {}",
+                    rank + 1,
+                    score.length,
+                    score.seconds,
+                    if score.perfect { '*' } else { ' ' }
+                )
+            })
+            .collect();
+        if scores.iter().any(|score| score.perfect) {
+            lines.push(String::from("* filled the board"));
+        }
+        lines
+    }
+
+    // Show the pause menu over the board until the player resumes, following terminal resizes
+    // meanwhile. Returns the state to switch to when the player restarts or quits instead.
+    fn pause(&mut self) -> Result<Option<GameState>, GameError> {
+        let mut menu: &[MenuEntry] = &PAUSE_MENU;
+        let mut selected = 0;
+        self.draw_pause_menu(menu, selected)?;
+        loop {
+            let keypress = self.input.wait_for_keypress(Some(INPUT_POLL));
+            match (keypress, self.navigate_menu(keypress, menu, &mut selected)) {
+                (KeyPress::Pause, _) | (_, MenuAction::Pick(MenuEntry::Resume)) => break,
+                (KeyPress::Quit, _) | (_, MenuAction::Pick(MenuEntry::Quit)) => {
+                    return Ok(Some(GameState::QuitButtonPressed))
+                }
+                (_, MenuAction::Pick(MenuEntry::Restart)) => {
+                    return Ok(Some(GameState::RestartGame))
+                }
+                (_, MenuAction::Pick(MenuEntry::Settings)) => {
+                    (menu, selected) = (&PAUSE_SETTINGS, 0)
+                }
+                (_, MenuAction::Pick(MenuEntry::Back)) => (menu, selected) = (&PAUSE_MENU, 2),
+                (_, MenuAction::Pick(_) | MenuAction::Redraw) => (),
+                // The countdown after resuming already restored the board
+                (KeyPress::Suspend, _) if self.suspend.is_some() => {
+                    return Ok((!self.suspend_round()?).then_some(GameState::QuitButtonPressed))
+                }
+                (_, MenuAction::Nothing) => {
+                    if self.poll_resize() {
+                        if !self.fit_board()? {
+                            return Ok(Some(GameState::QuitButtonPressed));
+                        }
+                        self.draw_pause_menu(menu, selected)?;
+                    }
+                    self.accept_spectators(true);
+                    continue;
+                }
+            }
+            self.draw_pause_menu(menu, selected)?;
+        }
+        // The countdown also puts back what the menu covered
+        match self.countdown()? {
+            true => Ok(None),
+            false => Ok(Some(GameState::QuitButtonPressed)),
+        }
+    }
+
+    // Draw the board with `menu` over it, since its pages differ in size
+    fn draw_pause_menu(&mut self, menu: &[MenuEntry], selected: usize) -> Result<(), GameError> {
+        self.output.clear_screen()?;
+        self.draw_all()?;
+        let (x_min, y_min, x_max, y_max) = self.board_corners();
+        self.draw_menu((x_min + x_max) / 2, (y_min + y_max) / 2, menu, selected)?;
+        self.output.render()
+    }
+
+    fn draw_menu(
+        &mut self,
+        x: u16,
+        y: u16,
+        menu: &[MenuEntry],
+        selected: usize,
+    ) -> Result<(), GameError> {
+        let title = if menu == MAIN_MENU {
+            "snake"
+        } else if menu == PAUSE_MENU {
+            "paused"
+        } else {
+            "settings"
+        };
+        let entries: Vec<String> = menu.iter().map(|&entry| self.menu_label(entry)).collect();
+        self.output.draw_menu(x, y, title, &entries, selected)
+    }
+
+    fn menu_label(&self, entry: MenuEntry) -> String {
+        let args = &self.args;
+        match entry {
+            MenuEntry::Mode => format!("Mode: < {} >", value_name(&args.mode)),
+            MenuEntry::Level => format!("Level: < {} >", args.speed),
+            MenuEntry::BoardSize => format!("Board: < {} >", value_name(&args.grid_size)),
+            MenuEntry::Keys => format!("Keys: < {} >", value_name(&args.movement_key_scheme)),
+            MenuEntry::Difficulty => format!("Difficulty: < {} >", value_name(&args.difficulty)),
+            MenuEntry::Opponents => format!("Opponents: < {} >", args.opponents),
+            MenuEntry::Skin => format!("Skin: < {} >", value_name(&args.skin)),
+            MenuEntry::HighScores => String::from("High Scores"),
+            entry => format!("{entry:?}"),
+        }
+    }
+
+    // Move through `menu` with the up and down keys, change settings with the left and right
+    // keys, and pick the `selected` entry with Enter
+    fn navigate_menu(
+        &mut self,
+        keypress: KeyPress,
+        menu: &[MenuEntry],
+        selected: &mut usize,
+    ) -> MenuAction {
+        let entry = menu[*selected];
+        match keypress {
+            KeyPress::DirectionKey(_, Direction::Up) => {
+                *selected = (*selected + menu.len() - 1) % menu.len();
+            }
+            KeyPress::DirectionKey(_, Direction::Down) => {
+                *selected = (*selected + 1) % menu.len();
+            }
+            KeyPress::DirectionKey(_, Direction::Left) if entry.is_setting() => {
+                self.change_setting(entry, -1);
+            }
+            KeyPress::DirectionKey(_, Direction::Right) | KeyPress::Select
+                if entry.is_setting() =>
+            {
+                self.change_setting(entry, 1);
+            }
+            KeyPress::Select => return MenuAction::Pick(entry),
+            _ => return MenuAction::Nothing,
+        }
+        MenuAction::Redraw
+    }
+
+    // Step the option behind a settings entry and remember the choice for the next run
+    fn change_setting(&mut self, entry: MenuEntry, step: isize) {
+        let args = &mut self.args;
+        match entry {
+            MenuEntry::Mode => args.mode = cycle_value(&args.mode, step),
+            MenuEntry::Level => args.speed = args.speed.cycle(step),
+            MenuEntry::BoardSize => args.grid_size = cycle_value(&args.grid_size, step),
+            MenuEntry::Keys => {
+                args.movement_key_scheme = cycle_value(&args.movement_key_scheme, step)
+            }
+            MenuEntry::Difficulty => args.difficulty = cycle_value(&args.difficulty, step),
+            MenuEntry::Opponents => {
+                args.opponents = (args.opponents as isize - 1 + step).rem_euclid(7) as u8 + 1
+            }
+            MenuEntry::Skin => args.skin = cycle_value(&args.skin, step),
+            _ => return,
+        }
+        self.output.set_skin(self.args.skin);
+        self.options = Options::from_args(self.args.clone());
+        self.ticker
+            .set_interval(Duration::from_millis(self.options.speed.value()));
+        self.input.set_key_schemes(&self.args.key_schemes());
+        if let Some(settings) = &self.settings {
+            // Not being able to save the settings should not end the game
+            let _ = settings.save(&self.args);
+        }
+    }
+
+    // Stop the program in the middle of a round. Once it continues, redraw the board and wait
+    // for 'p' and a countdown before play goes on. Returns false if the player quits instead.
+    fn suspend_round(&mut self) -> Result<bool, GameError> {
+        if !self.suspend_terminal()? {
+            return Ok(true);
+        }
+        let message = "Resumed - press 'p' to continue";
+        if !self.fit_board()? {
+            return Ok(false);
+        }
+        self.draw_board_message(message)?;
         loop {
-            match self.input.get_keypress() {
-                KeyPress::Pause => return GameState::RestartGame,
-                KeyPress::Quit => return GameState::QuitButtonPressed,
+            let keypress = self.input.wait_for_keypress(Some(INPUT_POLL));
+            match keypress {
+                KeyPress::Pause => break,
+                KeyPress::Quit => return Ok(false),
+                KeyPress::Suspend if self.suspend_terminal()? => {
+                    if !self.fit_board()? {
+                        return Ok(false);
+                    }
+                    self.draw_board_message(message)?;
+                }
                 _ => (),
             }
-            thread::sleep(Duration::from_millis(10));
+            if self.poll_resize() {
+                if !self.fit_board()? {
+                    return Ok(false);
+                }
+                self.draw_board_message(message)?;
+            }
+            self.accept_spectators(true);
+        }
+        self.countdown()
+    }
+
+    // Count down from 3 over the board before the snakes move. The last direction key each
+    // player presses meanwhile picks their starting heading. Returns false if the player quits.
+    fn countdown(&mut self) -> Result<bool, GameError> {
+        let mut turns = vec![None; self.instance.snakes.len()];
+        for count in (1..=COUNTDOWN_FROM).rev() {
+            self.draw_countdown(count)?;
+            let next = Instant::now() + COUNTDOWN_STEP;
+            while Instant::now() < next {
+                for keypress in self.input.get_keypresses() {
+                    match keypress {
+                        KeyPress::Quit => return Ok(false),
+                        KeyPress::DirectionKey(player, direction) if player < turns.len() => {
+                            turns[player] = Some(direction);
+                        }
+                        _ => (),
+                    }
+                }
+                if self.poll_resize() {
+                    if !self.fit_board()? {
+                        return Ok(false);
+                    }
+                    self.draw_countdown(count)?;
+                }
+                self.accept_spectators(true);
+                thread::sleep(INPUT_POLL);
+            }
+        }
+        // Turning only once keeps a snake from reversing onto itself
+        for (player, direction) in turns.into_iter().enumerate() {
+            if let Some(direction) = direction {
+                self.instance.turn(player, direction);
+            }
+        }
+        self.output.clear_screen()?;
+        self.draw_all()?;
+        self.output.render()?;
+        Ok(true)
+    }
+
+    fn draw_countdown(&mut self, count: u32) -> Result<(), GameError> {
+        self.output.clear_screen()?;
+        self.draw_all()?;
+        let (x_min, y_min, x_max, y_max) = self.board_corners();
+        self.output
+            .draw_countdown(x_min, x_max, y_min, y_max, count)?;
+        self.output.render()
+    }
+
+    // Hand the terminal back to the shell until the program is continued, then take in the
+    // terminal size. Returns false when suspending is not supported, as on telnet.
+    fn suspend_terminal(&mut self) -> Result<bool, GameError> {
+        let Some(suspend) = self.suspend.as_mut() else {
+            return Ok(false);
+        };
+        self.terminal_size = TerminalSize::new(suspend()?);
+        self.measure_cell_aspect();
+        // Keys pressed while stopped were meant for the shell
+        self.input.empty_key_buffer();
+        Ok(true)
+    }
+
+    // Take in a new terminal size, returning whether it changed
+    fn poll_resize(&mut self) -> bool {
+        let Some(size) = self.resize.as_mut().and_then(|resize| resize()) else {
+            return false;
+        };
+        if size == self.terminal_size.xy {
+            return false;
+        }
+        self.terminal_size = TerminalSize::new(size);
+        self.measure_cell_aspect();
+        true
+    }
+
+    // Take in the aspect of the terminal's cells, keeping the last one if it cannot be measured
+    fn measure_cell_aspect(&mut self) {
+        if let Some(aspect) = self.aspect.as_mut().and_then(|aspect| aspect()) {
+            self.cell_aspect = aspect;
+        }
+    }
+
+    // Centre the running board on the terminal and redraw it. While the terminal is too small
+    // for the board and its border, ask for a larger one. Returns false if the player quits.
+    fn fit_board(&mut self) -> Result<bool, GameError> {
+        let (width, height) = (self.instance.grid.width(), self.instance.grid.height());
+        let layout = self.options.layout();
+        loop {
+            let (term_x, term_y) = self.terminal_size.in_cells(layout).xy;
+            if term_x >= width + 2 && term_y >= height + 2 {
+                self.instance
+                    .move_to((term_x - width) / 2 + 1, (term_y - height) / 2 + 1);
+                self.output.clear_screen()?;
+                self.draw_all()?;
+                self.output.render()?;
+                return Ok(true);
+            }
+            let needed = layout.screen_size(width + 2, height + 2);
+            if !self.wait_for_resize(needed, true)? {
+                return Ok(false);
+            }
         }
     }
 
+    // Wait until the terminal is large enough to start a round. Returns false if the player
+    // quits meanwhile.
+    fn wait_for_room(&mut self) -> Result<bool, GameError> {
+        let needed = needed_terminal_size(&self.options);
+        loop {
+            let (term_x, term_y) = self.terminal_size.xy;
+            if term_x >= needed.0 && term_y >= needed.1 {
+                return Ok(true);
+            }
+            if !self.wait_for_resize(needed, false)? {
+                return Ok(false);
+            }
+        }
+    }
+
+    // Ask for a terminal of at least `needed` until the size changes, while a round is
+    // `running` or not. Returns false if the player quits instead.
+    fn wait_for_resize(&mut self, needed: (u16, u16), running: bool) -> Result<bool, GameError> {
+        self.output.clear_screen()?;
+        self.output.draw_too_small_message(needed.0, needed.1)?;
+        self.output.render()?;
+        while !self.poll_resize() {
+            if let KeyPress::Quit = self.input.wait_for_keypress(Some(INPUT_POLL)) {
+                return Ok(false);
+            }
+            self.accept_spectators(running);
+        }
+        Ok(true)
+    }
+
     fn restart_game(&mut self) -> GameState {
-        self.instance = GameInstance::new(&self.terminal_size, self.options.grid_size.value());
+        self.instance = Self::new_instance(&self.options, &self.terminal_size);
         GameState::InProgress
     }
 
-    fn game_over_transition(&mut self) -> GameState {
+    fn new_instance(options: &Options, terminal_size: &TerminalSize) -> GameInstance {
+        let terminal_size = &terminal_size.in_cells(options.layout());
+        let grid_size = options.grid_size.value();
+        match options.mode {
+            GameMode::SinglePlayer => GameInstance::new(terminal_size, grid_size),
+            GameMode::TwoPlayer => GameInstance::new_two_player(terminal_size, grid_size),
+            GameMode::Versus => {
+                GameInstance::new_versus(terminal_size, grid_size, options.versus.opponents)
+            }
+        }
+    }
+
+    // Let every living computer-controlled snake pick its next direction
+    fn steer_opponents(&mut self) {
+        if self.options.mode != GameMode::Versus {
+            return;
+        }
+        for player in 1..self.instance.snakes.len() {
+            if self.instance.snakes[player].alive {
+                let direction = ai::choose_direction(
+                    &self.instance,
+                    player,
+                    self.options.versus.difficulty,
+                    &mut self.opponent_rng,
+                );
+                self.instance.turn(player, direction);
+            }
+        }
+    }
+
+    // A single-player round ends when the snake dies, a two-player round on the first death.
+    // In versus mode the round ends when the player dies, which loses it, or when the win
+    // condition is met.
+    fn round_over(&self) -> bool {
+        let alive = self.instance.alive_count();
+        match self.options.mode {
+            GameMode::SinglePlayer => alive == 0,
+            GameMode::TwoPlayer => alive < self.instance.snakes.len(),
+            GameMode::Versus => {
+                !self.instance.snakes[0].alive
+                    || match self.options.versus.win_condition {
+                        WinCondition::LastStanding => alive <= 1,
+                        WinCondition::MostFood => self.played() >= self.options.versus.time_limit,
+                    }
+            }
+        }
+    }
+
+    // Time the round has been played so far
+    fn played(&self) -> Duration {
+        self.round_time
+            + self
+                .running_since
+                .map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    // Count the time from now on towards the round
+    fn start_clock(&mut self) {
+        self.running_since.get_or_insert_with(Instant::now);
+    }
+
+    // Stop counting the time towards the round, while it is paused or over
+    fn stop_clock(&mut self) {
+        if let Some(since) = self.running_since.take() {
+            self.round_time += since.elapsed();
+        }
+    }
+
+    // Index of the snake that won the finished round, if any
+    fn winner(&self) -> Option<usize> {
+        let snakes = &self.instance.snakes;
+        match (self.options.mode, self.options.versus.win_condition) {
+            (GameMode::Versus, WinCondition::MostFood) => {
+                // Most food wins, a tie for first place is a draw. Dying loses the round for the
+                // player, so it is ranked among the opponents only while alive.
+                let first = if snakes[0].alive { 0 } else { 1 };
+                let most = snakes[first..].iter().map(|snake| snake.food_eaten).max()?;
+                let mut leaders = (first..snakes.len()).filter(|&i| snakes[i].food_eaten == most);
+                match (leaders.next(), leaders.next()) {
+                    (Some(player), None) => Some(player),
+                    _ => None,
+                }
+            }
+            // The last snake standing wins, a head-to-head crash is a draw
+            _ => match self.instance.alive_count() {
+                1 => snakes.iter().position(|snake| snake.alive),
+                _ => None,
+            },
+        }
+    }
+
+    // Whether `player` is the one at the keyboard, who is addressed as "you"
+    fn is_you(&self, player: usize) -> bool {
+        match self.options.mode {
+            GameMode::SinglePlayer | GameMode::Versus => player == 0,
+            GameMode::TwoPlayer => false,
+        }
+    }
+
+    fn player_name(&self, player: usize) -> String {
+        if self.is_you(player) {
+            return String::from("you");
+        }
+        match self.options.mode {
+            GameMode::TwoPlayer => format!("player {}", player + 1),
+            GameMode::SinglePlayer | GameMode::Versus => format!("cpu {player}"),
+        }
+    }
+
+    // How, when and where a snake died, with the cell counted from the board's top left corner
+    fn death_report(&self, player: usize) -> Option<String> {
+        let death = self.instance.snakes[player].death?;
+        let name = self.player_name(player);
+        let cause = match death.cause {
+            DeathCause::OwnBody if self.is_you(player) => String::from("ran into yourself"),
+            DeathCause::OwnBody => String::from("ran into itself"),
+            DeathCause::Snake(other) => format!("ran into {}", self.player_name(other)),
+            DeathCause::Timeout => String::from("timed out"),
+        };
+        let grid = &self.instance.grid;
+        Some(format!(
+            "{name} {cause} on tick {} at ({}, {}).",
+            death.tick,
+            death.cell.x - grid.x_min,
+            death.cell.y - grid.y_min
+        ))
+    }
+
+    // Title and per-snake result lines of a finished multi-snake round
+    fn round_results(&self) -> (String, Vec<String>) {
+        let snakes = &self.instance.snakes;
+        let name = |player: usize| self.player_name(player);
+        let winner = self.winner();
+        let title = match winner {
+            Some(0) if self.options.mode == GameMode::Versus => String::from("you|win!"),
+            Some(player) => format!("{}|wins!", name(player)),
+            // The player died while several opponents are still alive, or with opponents tied
+            // for the most food
+            None if self.options.mode == GameMode::Versus
+                && !snakes[0].alive
+                && (self.options.versus.win_condition == WinCondition::MostFood
+                    || self.instance.alive_count() > 0) =>
+            {
+                String::from("you|lose!")
+            }
+            None => String::from("it's a|draw!"),
+        };
+        let mut results = Vec::new();
+        for (player, snake) in snakes.iter().enumerate() {
+            results.push(format!(
+                "{}: length {}, food {}",
+                name(player),
+                snake.body.len(),
+                snake.food_eaten
+            ));
+            results.extend(self.death_report(player));
+        }
+        results.extend(self.dropped_ticks());
+        (title, results)
+    }
+
+    // Remember how the round went and enter single-player results into the high scores
+    fn finish_round(&mut self, perfect: bool) {
+        self.stop_clock();
+        self.round_rank = None;
+        self.round_dropped = self.ticker.dropped();
+        if self.options.mode != GameMode::SinglePlayer {
+            return;
+        }
+        let Some(high_scores) = &mut self.high_scores else {
+            return;
+        };
+        let snake = &self.instance.snakes[0];
+        let score = Score {
+            length: snake.body.len(),
+            food: snake.food_eaten,
+            seconds: self.round_time.as_secs(),
+            perfect,
+        };
+        // Not being able to save the high scores should not end the game
+        self.round_rank = high_scores.record(score).ok().flatten();
+    }
+
+    // Tells the player that the last round ran slower than it should have, if it did
+    fn dropped_ticks(&self) -> Option<String> {
+        let plural = if self.round_dropped == 1 { "" } else { "s" };
+        (self.round_dropped > 0).then(|| {
+            format!(
+                "The game fell behind and skipped {} tick{plural}.",
+                self.round_dropped
+            )
+        })
+    }
+
+    // Result lines of a round the snake won by filling the board
+    fn victory_stats(&self) -> Vec<String> {
+        let snake = &self.instance.snakes[0];
+        let mut stats = vec![
+            format!("You filled the board in {}s!", self.round_time.as_secs()),
+            format!("Length {}, food {}.", snake.body.len(), snake.food_eaten),
+        ];
+        match (
+            self.round_rank,
+            self.high_scores.as_ref().and_then(HighScores::best),
+        ) {
+            (Some(0), _) => stats.push(String::from("A perfect game and a new high score!")),
+            (Some(rank), _) => stats.push(format!(
+                "A perfect game, number {} in the high scores!",
+                rank + 1
+            )),
+            (None, Some(best)) => {
+                stats.push(format!("The high score is a length of {}.", best.length))
+            }
+            (None, None) => stats.push(String::from("A perfect game!")),
+        }
+        stats.extend(self.dropped_ticks());
+        stats
+    }
+
+    // Dead snakes are removed from the board, as they are for watchers, except the one of the
+    // player at the keyboard, whose death ends the round anyway
+    fn snake_visible(&self, player: usize) -> bool {
+        self.instance.snakes[player].alive || self.is_you(player)
+    }
+
+    fn game_over_transition(&mut self) -> Result<GameState, GameError> {
         let transition_time = 500;
         let num_changes = 3;
+        let (_, y_min, _, y_max) = self.board_corners();
         for _ in 1..=num_changes {
-            self.output
-                .draw_game_over_transition_msg(self.instance.grid.y_min, self.instance.grid.y_max);
-            self.draw_border();
-            self.output.render();
+            self.output.draw_game_over_transition_msg(y_min, y_max)?;
+            self.draw_border()?;
+            self.output.render()?;
             thread::sleep(Duration::from_millis(transition_time));
-            self.output.clear_screen();
-            self.draw_all();
-            self.output.render();
+            self.output.clear_screen()?;
+            self.draw_all()?;
+            self.draw_fatal_cells()?;
+            self.output.render()?;
             thread::sleep(Duration::from_millis(transition_time));
         }
-        GameState::GameOver
+        Ok(GameState::GameOver)
+    }
+
+    // New watchers receive the current round only while one is `running`
+    fn accept_spectators(&mut self, running: bool) {
+        if let Some(spectators) = &mut self.spectators {
+            spectators.accept(running.then_some(&self.instance));
+        }
+    }
+
+    fn broadcast_to_spectators(&mut self, message: &ServerMessage) {
+        if let Some(spectators) = &mut self.spectators {
+            spectators.broadcast(message);
+        }
+    }
+
+    fn draw_border(&mut self) -> Result<(), GameError> {
+        let (x_min, y_min, x_max, y_max) = self.instance.grid.get_corners();
+        self.output.draw_border(x_min, x_max, y_min, y_max)
+    }
+
+    fn draw_snakes(&mut self) -> Result<(), GameError> {
+        let visible: Vec<usize> = (0..self.instance.snakes.len())
+            .filter(|&player| self.snake_visible(player))
+            .collect();
+        let snakes = &self.instance.snakes;
+        self.output
+            .draw_snakes(visible.into_iter().map(|player| (player, &snakes[player])))
+    }
+
+    fn draw_board_message(&mut self, message: &str) -> Result<(), GameError> {
+        let (x_min, y_min, x_max, y_max) = self.board_corners();
+        self.output
+            .draw_board_message(x_min, x_max, (y_min + y_max) / 2, message)?;
+        self.output.render()
+    }
+
+    // Mark where the snakes that died on the last tick crashed
+    fn draw_fatal_cells(&mut self) -> Result<(), GameError> {
+        let ticks = self.instance.ticks;
+        for snake in &self.instance.snakes {
+            if let Some(death) = snake.death.filter(|death| death.tick == ticks) {
+                self.output.draw_fatal_cell(&death.cell)?;
+            }
+        }
+        Ok(())
     }
 
-    fn draw_border(&mut self) {
+    // Corners of the board on the screen, which differ from the grid's unless every cell is one
+    // character
+    fn board_corners(&self) -> (u16, u16, u16, u16) {
         let (x_min, y_min, x_max, y_max) = self.instance.grid.get_corners();
-        self.output.draw_border(x_min, x_max, y_min, y_max);
+        let layout = self.output.layout();
+        let (x_min, x_max) = layout.columns(x_min, x_max);
+        let (y_min, y_max) = layout.rows(y_min, y_max);
+        (x_min, y_min, x_max, y_max)
     }
 
-    fn draw_all(&mut self) {
-        self.draw_border();
-        self.output.draw_snake(&self.instance.snake);
-        self.output.draw_food(&self.instance.food);
+    fn draw_all(&mut self) -> Result<(), GameError> {
+        self.draw_border()?;
+        self.draw_snakes()?;
+        self.output.draw_food(&self.instance.food)
     }
 }
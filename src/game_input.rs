@@ -1,35 +1,62 @@
 use std::collections::HashMap;
 
+use std::io;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
 use termion::event::Key;
-use termion::AsyncReader;
 
 use crate::game_instance::Direction;
 use crate::parser::MovementKeyScheme;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum KeyPress {
-    DirectionKey(Direction),
+    // Direction pressed by the player with the given index
+    DirectionKey(usize, Direction),
     Quit,
     Pause,
+    // Enter, which picks the highlighted menu entry
+    Select,
+    // Ctrl-Z, which raw mode delivers as a key instead of a signal
+    Suspend,
     Other,
     None,
 }
 
-// TODO: still need to figure out how to abstract this part properly
+// Key presses read from a blocking source on a background thread. Once the source ends or
+// fails, for example when a remote player hangs up, every poll sees the quit key.
 pub struct GameInput {
-    pub input: termion::input::Keys<AsyncReader>,
+    keys: Receiver<io::Result<Key>>,
     pub keybinds: HashMap<termion::event::Key, KeyPress>,
 }
 
 impl GameInput {
-    pub fn new(input: termion::input::Keys<AsyncReader>, key_scheme: MovementKeyScheme) -> Self {
+    pub fn new(
+        input: impl Iterator<Item = io::Result<Key>> + Send + 'static,
+        key_schemes: &[MovementKeyScheme],
+    ) -> Self {
+        let (sender, keys) = mpsc::channel();
+        thread::spawn(move || {
+            // A failed read usually fails again, so stop at the first error
+            for key in input {
+                let failed = key.is_err();
+                if sender.send(key).is_err() || failed {
+                    break;
+                }
+            }
+        });
         Self {
-            input,
-            keybinds: Self::create_keybinds(key_scheme),
+            keys,
+            keybinds: Self::create_keybinds(key_schemes),
         }
     }
+    // Rebind the movement keys, for example after the game mode changed
+    pub fn set_key_schemes(&mut self, key_schemes: &[MovementKeyScheme]) {
+        self.keybinds = Self::create_keybinds(key_schemes);
+    }
     fn create_keybinds(
-        movement_key_scheme: MovementKeyScheme,
+        movement_key_schemes: &[MovementKeyScheme],
     ) -> HashMap<termion::event::Key, KeyPress> {
         let mut keybinds = HashMap::new();
 
@@ -39,38 +66,151 @@ impl GameInput {
         // Insert Pause button
         keybinds.insert(Key::Char('p'), KeyPress::Pause);
         keybinds.insert(Key::Char('P'), KeyPress::Pause);
-        // Insert direction buttons
-        match movement_key_scheme {
-            MovementKeyScheme::Arrows => {
-                keybinds.insert(Key::Up, KeyPress::DirectionKey(Direction::Up));
-                keybinds.insert(Key::Down, KeyPress::DirectionKey(Direction::Down));
-                keybinds.insert(Key::Left, KeyPress::DirectionKey(Direction::Left));
-                keybinds.insert(Key::Right, KeyPress::DirectionKey(Direction::Right));
-            }
-            MovementKeyScheme::Wsad => {
-                keybinds.insert(Key::Char('w'), KeyPress::DirectionKey(Direction::Up));
-                keybinds.insert(Key::Char('s'), KeyPress::DirectionKey(Direction::Down));
-                keybinds.insert(Key::Char('a'), KeyPress::DirectionKey(Direction::Left));
-                keybinds.insert(Key::Char('d'), KeyPress::DirectionKey(Direction::Right));
+        // Insert Select button
+        keybinds.insert(Key::Char('\n'), KeyPress::Select);
+        // Insert Suspend button
+        keybinds.insert(Key::Ctrl('z'), KeyPress::Suspend);
+        // Insert direction buttons for every player
+        for (player, movement_key_scheme) in movement_key_schemes.iter().enumerate() {
+            let direction_key = |direction| KeyPress::DirectionKey(player, direction);
+            match movement_key_scheme {
+                MovementKeyScheme::Arrows => {
+                    keybinds.insert(Key::Up, direction_key(Direction::Up));
+                    keybinds.insert(Key::Down, direction_key(Direction::Down));
+                    keybinds.insert(Key::Left, direction_key(Direction::Left));
+                    keybinds.insert(Key::Right, direction_key(Direction::Right));
+                }
+                MovementKeyScheme::Wsad => {
+                    keybinds.insert(Key::Char('w'), direction_key(Direction::Up));
+                    keybinds.insert(Key::Char('s'), direction_key(Direction::Down));
+                    keybinds.insert(Key::Char('a'), direction_key(Direction::Left));
+                    keybinds.insert(Key::Char('d'), direction_key(Direction::Right));
+                }
             }
         }
         keybinds
     }
 
-    pub fn get_keypress(&mut self) -> &KeyPress {
-        match self.input.by_ref().last() {
-            Some(result) => {
-                let key = result.unwrap();
-                match self.keybinds.contains_key(&key) {
-                    true => self.keybinds.get(&key).unwrap(),
-                    false => &KeyPress::Other,
+    // The next key pressed, in the order they were pressed. Keys are queued, so a poll never
+    // loses any.
+    pub fn get_keypress(&mut self) -> KeyPress {
+        match self.keys.try_recv() {
+            Ok(key) => self.keypress(key),
+            Err(TryRecvError::Empty) => KeyPress::None,
+            Err(TryRecvError::Disconnected) => KeyPress::Quit,
+        }
+    }
+
+    // Wait up to `timeout`, or for good without one, until a key is pressed
+    pub fn wait_for_keypress(&mut self, timeout: Option<Duration>) -> KeyPress {
+        let key = match timeout {
+            Some(timeout) => self.keys.recv_timeout(timeout),
+            None => self.keys.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match key {
+            Ok(key) => self.keypress(key),
+            Err(RecvTimeoutError::Timeout) => KeyPress::None,
+            Err(RecvTimeoutError::Disconnected) => KeyPress::Quit,
+        }
+    }
+
+    // Every key pressed since the last call, oldest first
+    pub fn get_keypresses(&mut self) -> Vec<KeyPress> {
+        let mut keypresses = Vec::new();
+        loop {
+            match self.get_keypress() {
+                KeyPress::None => return keypresses,
+                KeyPress::Quit => {
+                    keypresses.push(KeyPress::Quit);
+                    return keypresses;
                 }
+                keypress => keypresses.push(keypress),
             }
-            None => &KeyPress::None,
         }
     }
 
     pub fn empty_key_buffer(&mut self) {
-        self.input.by_ref().last();
+        while self.keys.try_recv().is_ok() {}
+    }
+
+    // A failed read is the last thing the input sends, so it counts as the quit key like the
+    // end of the input does
+    fn keypress(&self, key: io::Result<Key>) -> KeyPress {
+        match key {
+            Ok(key) => *self.keybinds.get(&key).unwrap_or(&KeyPress::Other),
+            Err(_) => KeyPress::Quit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc::Sender;
+
+    // Input fed by the test through the returned sender, ending when it is dropped
+    fn input() -> (GameInput, Sender<io::Result<Key>>) {
+        let (sender, keys) = mpsc::channel();
+        let input = GameInput::new(keys.into_iter(), &[MovementKeyScheme::Arrows]);
+        (input, sender)
+    }
+
+    // Wait until the background thread has passed on everything sent so far
+    fn settle(input: &mut GameInput, count: usize) -> Vec<KeyPress> {
+        let mut keypresses = Vec::new();
+        while keypresses.len() < count {
+            keypresses.push(input.wait_for_keypress(Some(Duration::from_secs(5))));
+        }
+        keypresses
+    }
+
+    #[test]
+    fn keys_are_queued_in_order() {
+        let (mut input, keys) = input();
+        for key in [Key::Up, Key::Char('x'), Key::Left, Key::Char('p')] {
+            keys.send(Ok(key)).unwrap();
+        }
+        assert_eq!(
+            settle(&mut input, 1),
+            [KeyPress::DirectionKey(0, Direction::Up)]
+        );
+        // Give the remaining keys time to arrive
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(input.get_keypress(), KeyPress::Other);
+        assert_eq!(
+            input.get_keypresses(),
+            [KeyPress::DirectionKey(0, Direction::Left), KeyPress::Pause]
+        );
+        assert_eq!(input.get_keypress(), KeyPress::None);
+        assert_eq!(
+            input.wait_for_keypress(Some(Duration::from_millis(10))),
+            KeyPress::None
+        );
+    }
+
+    #[test]
+    fn a_read_error_quits_instead_of_panicking() {
+        let (mut input, keys) = input();
+        keys.send(Ok(Key::Down)).unwrap();
+        keys.send(Err(io::Error::other("terminal went away")))
+            .unwrap();
+        // Never passed on, since the input stops at the error
+        let _ = keys.send(Ok(Key::Up));
+        assert_eq!(
+            settle(&mut input, 2),
+            [KeyPress::DirectionKey(0, Direction::Down), KeyPress::Quit]
+        );
+        assert_eq!(input.get_keypress(), KeyPress::Quit);
+        assert_eq!(input.get_keypresses(), [KeyPress::Quit]);
+    }
+
+    #[test]
+    fn the_end_of_the_input_quits() {
+        let (mut input, keys) = input();
+        drop(keys);
+        assert_eq!(input.wait_for_keypress(None), KeyPress::Quit);
+        assert_eq!(input.get_keypress(), KeyPress::Quit);
+        assert_eq!(input.get_keypresses(), [KeyPress::Quit]);
     }
 }
@@ -1,6 +1,7 @@
 use std::collections::VecDeque;
 
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 use crate::game::TerminalSize;
 
@@ -10,7 +11,7 @@ pub struct GridCell {
     pub y: u16,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Direction {
     Up,
     Down,
@@ -19,6 +20,13 @@ pub enum Direction {
 }
 
 impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
     pub fn vertical(&self) -> bool {
         match self {
             Self::Up | Self::Down => true,
@@ -27,27 +35,79 @@ impl Direction {
     }
 }
 
+// Why a snake died
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DeathCause {
+    // Ran into its own body
+    OwnBody,
+    // Ran into the snake of the given player
+    Snake(usize),
+    // Its player stopped sending moves, for example by leaving a network game
+    Timeout,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Death {
+    pub cause: DeathCause,
+    // Number of the tick the snake died on, counting from 1
+    pub tick: u64,
+    // Cell the head was in when the snake died
+    pub cell: GridCell,
+}
+
+// What happened to a living snake during a tick
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TickOutcome {
+    Moved,
+    AteFood,
+    Died(DeathCause),
+}
+
+// Result of `GameInstance::game_cycle`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Tick {
+    // Outcome for every snake, `None` for snakes that were already dead
+    pub outcomes: Vec<Option<TickOutcome>>,
+    // The living snakes fill every cell of the grid, which wins the round
+    pub board_full: bool,
+}
+
 const INIT_SNAKE_SIZE: u16 = 5;
 #[derive(Debug)]
 pub struct Snake {
     pub body: VecDeque<GridCell>,
     pub old_tail: Option<GridCell>,
+    pub direction: Direction,
+    pub alive: bool,
+    pub food_eaten: usize,
+    pub death: Option<Death>,
 }
 
 impl Snake {
     fn new(grid: &GameGrid) -> Self {
+        Self::on_row(grid, (grid.y_max + grid.y_min) / 2, Direction::Left)
+    }
+    // Create a snake on row `y` at the edge it moves away from
+    fn on_row(grid: &GameGrid, y: u16, direction: Direction) -> Self {
         let mut body = VecDeque::new();
-        let (_, y_min, x_max, y_max) = grid.get_corners();
         for i in 1..=INIT_SNAKE_SIZE {
-            body.push_front(GridCell {
-                x: x_max - i,
-                y: (y_max + y_min) / 2,
-            });
+            let x = match direction {
+                Direction::Right => grid.x_min + i,
+                _ => grid.x_max - i,
+            };
+            body.push_front(GridCell { x, y });
         }
         let old_tail = None;
-        Self { body, old_tail }
+        Self {
+            body,
+            old_tail,
+            direction,
+            alive: true,
+            food_eaten: 0,
+            death: None,
+        }
     }
-    fn get_head(&self) -> &GridCell {
+    pub fn get_head(&self) -> &GridCell {
         self.body.front().unwrap()
     }
     fn add_head(&mut self, head: GridCell) {
@@ -73,11 +133,23 @@ pub struct GameGrid {
 }
 
 impl GameGrid {
+    // The snake starts INIT_SNAKE_SIZE cells long on a single row, so a grid built with
+    // `with_dimensions` must be wider than that
+    pub const MIN_WIDTH: u16 = INIT_SNAKE_SIZE + 1;
+    pub const MIN_HEIGHT: u16 = 1;
+    // Largest grid handed out to callers that pick arbitrary dimensions, every cell is
+    // allocated up front and scanned when placing food
+    pub const MAX_CELLS: u32 = 1 << 20;
+
+    // Whether `with_dimensions` accepts a `width` x `height` grid
+    pub fn fits(width: u16, height: u16) -> bool {
+        width >= Self::MIN_WIDTH
+            && height >= Self::MIN_HEIGHT
+            && u32::from(width) * u32::from(height) <= Self::MAX_CELLS
+    }
+
     fn new(terminal_size: &TerminalSize, percent: f64) -> Self {
-        let x_min = (TERM_MIN_COORD + ((terminal_size.x() - 1) as f64 * (1.0 - percent))) as u16;
-        let y_min = (TERM_MIN_COORD + ((terminal_size.y() - 1) as f64 * (1.0 - percent))) as u16;
-        let x_max = ((terminal_size.x() - 1) as f64 * percent) as u16;
-        let y_max = ((terminal_size.y() - 1) as f64 * percent) as u16;
+        let (x_min, y_min, x_max, y_max) = Self::bounds(terminal_size, percent);
         let cells = Self::fill_cells(x_min, y_min, x_max, y_max);
         Self {
             x_min,
@@ -88,6 +160,49 @@ impl GameGrid {
         }
     }
 
+    // Corners of the grid covering `percent` of the terminal. Tiny terminals get a grid of at
+    // least one cell, which `Game` never starts a round on.
+    fn bounds(terminal_size: &TerminalSize, percent: f64) -> (u16, u16, u16, u16) {
+        let term_x = terminal_size.x().saturating_sub(1) as f64;
+        let term_y = terminal_size.y().saturating_sub(1) as f64;
+        let x_min = (TERM_MIN_COORD + (term_x * (1.0 - percent))) as u16;
+        let y_min = (TERM_MIN_COORD + (term_y * (1.0 - percent))) as u16;
+        let x_max = ((term_x * percent) as u16).max(x_min);
+        let y_max = ((term_y * percent) as u16).max(y_min);
+        (x_min, y_min, x_max, y_max)
+    }
+
+    // Width and height of the grid `new` makes for a terminal
+    pub fn size_for(terminal_size: &TerminalSize, percent: f64) -> (u16, u16) {
+        let (x_min, y_min, x_max, y_max) = Self::bounds(terminal_size, percent);
+        (x_max - x_min + 1, y_max - y_min + 1)
+    }
+
+    pub fn with_dimensions(width: u16, height: u16) -> Self {
+        Self::with_origin(0, 0, width, height)
+    }
+
+    // Grid of `width` x `height` cells whose top-left cell is at (x_min, y_min)
+    pub fn with_origin(x_min: u16, y_min: u16, width: u16, height: u16) -> Self {
+        let (x_max, y_max) = (x_min + width - 1, y_min + height - 1);
+        let cells = Self::fill_cells(x_min, y_min, x_max, y_max);
+        Self {
+            x_min,
+            y_min,
+            x_max,
+            y_max,
+            cells,
+        }
+    }
+
+    pub fn width(&self) -> u16 {
+        self.x_max - self.x_min + 1
+    }
+
+    pub fn height(&self) -> u16 {
+        self.y_max - self.y_min + 1
+    }
+
     fn fill_cells(x_min: u16, y_min: u16, x_max: u16, y_max: u16) -> Vec<GridCell> {
         let mut cells = Vec::new();
         for i in x_min..=x_max {
@@ -98,6 +213,49 @@ impl GameGrid {
         cells
     }
 
+    // Cell reached by moving one step from `head` in `direction`
+    pub fn next_cell(&self, head: &GridCell, direction: Direction) -> GridCell {
+        match direction {
+            // If snake is at an edge, wrap around to other side
+            Direction::Right => {
+                let x = if head.x == self.x_max {
+                    self.x_min
+                } else {
+                    head.x + 1
+                };
+                let y = head.y;
+                GridCell { x, y }
+            }
+            Direction::Left => {
+                let x = if head.x == self.x_min {
+                    self.x_max
+                } else {
+                    head.x - 1
+                };
+                let y = head.y;
+                GridCell { x, y }
+            }
+            Direction::Up => {
+                let x = head.x;
+                let y = if head.y == self.y_min {
+                    self.y_max
+                } else {
+                    head.y - 1
+                };
+                GridCell { x, y }
+            }
+            Direction::Down => {
+                let x = head.x;
+                let y = if head.y == self.y_max {
+                    self.y_min
+                } else {
+                    head.y + 1
+                };
+                GridCell { x, y }
+            }
+        }
+    }
+
     pub fn get_corners(&self) -> (u16, u16, u16, u16) {
         (self.x_min, self.y_min, self.x_max, self.y_max)
     }
@@ -105,9 +263,13 @@ impl GameGrid {
 
 pub struct GameInstance {
     pub grid: GameGrid,
-    pub snake: Snake,
+    pub snakes: Vec<Snake>,
     pub food: GridCell,
-    pub direction: Direction,
+    // Seed of the food sequence, which together with the moves replays a round
+    pub seed: u64,
+    // Number of ticks played so far
+    pub ticks: u64,
+    rng: StdRng,
 }
 
 impl GameInstance {
@@ -115,137 +277,334 @@ impl GameInstance {
         // Initialize grid
         let grid = GameGrid::new(terminal_size, grid_size);
         // Initialize snake
-        let snake = Snake::new(&grid);
-        // Generate food in a random cell
-        let food = Self::generate_random_food(&grid.cells, &snake);
-        // Initialize starting movement direction
-        let direction = Direction::Left;
-        Self {
-            grid,
-            snake,
-            food,
-            direction,
-        }
+        let snakes = vec![Snake::new(&grid)];
+        Self::with_snakes(grid, snakes, rand::random())
+    }
+
+    // Create an instance on an existing grid, with a reproducible food sequence
+    pub fn with_seed(grid: GameGrid, seed: u64) -> Self {
+        let snakes = vec![Snake::new(&grid)];
+        Self::with_snakes(grid, snakes, seed)
+    }
+
+    pub fn new_two_player(terminal_size: &TerminalSize, grid_size: f64) -> Self {
+        // Initialize grid
+        let grid = GameGrid::new(terminal_size, grid_size);
+        // Player 1 starts on the upper third heading left, player 2 on the lower third heading right
+        let height = grid.y_max - grid.y_min;
+        let snakes = vec![
+            Snake::on_row(&grid, grid.y_min + height / 3, Direction::Left),
+            Snake::on_row(&grid, grid.y_max - height / 3, Direction::Right),
+        ];
+        Self::with_snakes(grid, snakes, rand::random())
+    }
+
+    // The player plus `opponents` computer snakes, spread evenly over the rows of the grid
+    pub fn new_versus(terminal_size: &TerminalSize, grid_size: f64, opponents: usize) -> Self {
+        // Initialize grid
+        let grid = GameGrid::new(terminal_size, grid_size);
+        Self::with_players(grid, opponents + 1)
+    }
+
+    // `players` snakes spread evenly over the rows of an existing grid, alternating direction
+    pub fn with_players(grid: GameGrid, players: usize) -> Self {
+        let count = players as u16;
+        let height = grid.y_max - grid.y_min;
+        let snakes = (0..count)
+            .map(|i| {
+                let y = grid.y_min + (i + 1) * height / (count + 1);
+                let direction = if i % 2 == 0 {
+                    Direction::Left
+                } else {
+                    Direction::Right
+                };
+                Snake::on_row(&grid, y, direction)
+            })
+            .collect();
+        Self::with_snakes(grid, snakes, rand::random())
     }
 
     pub fn new_pre_game(terminal_size: &TerminalSize) -> Self {
         // Initialize grid
         let grid = GameGrid::new(terminal_size, 1.0);
         // Initialize snake
-        let mut snake = Snake::new(&grid);
-        let (_, _, x_max, y_max) = grid.get_corners();
-        snake.body.clear();
-        for i in 1..=INIT_SNAKE_SIZE {
-            snake.body.push_front(GridCell {
-                x: x_max - i,
-                y: (y_max + (y_max / 2)) / 2,
-            });
-        }
-        snake.old_tail = None;
-        // Generate food in a random cell
-        let food = Self::generate_random_food(&grid.cells, &snake);
-        // Initialize starting movement direction
-        let direction = Direction::Left;
+        let y = (grid.y_max + (grid.y_max / 2)) / 2;
+        let snakes = vec![Snake::on_row(&grid, y, Direction::Left)];
+        Self::with_snakes(grid, snakes, rand::random())
+    }
+
+    fn with_snakes(grid: GameGrid, snakes: Vec<Snake>, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        // Generate food in a random cell, or the first one if the snakes already fill the grid
+        let food = Self::generate_random_food(&grid.cells, &snakes, &mut rng).unwrap_or(GridCell {
+            x: grid.x_min,
+            y: grid.y_min,
+        });
         Self {
             grid,
-            snake,
+            snakes,
             food,
-            direction,
+            seed,
+            ticks: 0,
+            rng,
+        }
+    }
+
+    // Change a snake's movement direction, ignoring turns back onto its own body
+    pub fn turn(&mut self, player: usize, direction: Direction) {
+        let snake = &mut self.snakes[player];
+        if direction.vertical() != snake.direction.vertical() {
+            snake.direction = direction;
         }
     }
 
-    // return false if game over, else true
-    pub fn game_cycle(&mut self) -> bool {
+    // Move the whole game so that the top-left cell of the grid ends up at (x_min, y_min)
+    pub fn move_to(&mut self, x_min: u16, y_min: u16) {
+        let (old_x, old_y) = (self.grid.x_min, self.grid.y_min);
+        let shift = |cell: &mut GridCell| {
+            cell.x = cell.x - old_x + x_min;
+            cell.y = cell.y - old_y + y_min;
+        };
+        for snake in &mut self.snakes {
+            snake.body.iter_mut().for_each(shift);
+            snake.old_tail.as_mut().map(shift);
+            if let Some(death) = &mut snake.death {
+                shift(&mut death.cell);
+            }
+        }
+        shift(&mut self.food);
+        self.grid = GameGrid::with_origin(x_min, y_min, self.grid.width(), self.grid.height());
+    }
+
+    pub fn alive_count(&self) -> usize {
+        self.snakes.iter().filter(|snake| snake.alive).count()
+    }
+
+    // Kill a living snake outside of the game rules, like when its player leaves
+    pub fn kill(&mut self, player: usize, cause: DeathCause) {
+        let snake = &mut self.snakes[player];
+        if snake.alive {
+            snake.alive = false;
+            snake.death = Some(Death {
+                cause,
+                tick: self.ticks,
+                cell: *snake.get_head(),
+            });
+        }
+    }
+
+    pub fn game_cycle(&mut self) -> Tick {
+        self.ticks += 1;
+        let mut outcomes: Vec<Option<TickOutcome>> = self
+            .snakes
+            .iter()
+            .map(|snake| snake.alive.then_some(TickOutcome::Moved))
+            .collect();
+
         // Handle snake movement
-        self.move_snake();
+        for player in 0..self.snakes.len() {
+            if self.snakes[player].alive {
+                self.move_snake(player);
+            }
+        }
 
-        // Handle snake colliding with itself
-        if self.check_collision() {
-            return false;
+        // Handle snakes eating food
+        let mut food_eaten = false;
+        for (player, snake) in self.snakes.iter_mut().enumerate() {
+            if snake.alive && snake.get_head() == &self.food {
+                // Add another segment to the snake by restoring his old tail segment
+                snake.restore_tail();
+                snake.food_eaten += 1;
+                food_eaten = true;
+                outcomes[player] = Some(TickOutcome::AteFood);
+            }
+        }
+
+        // Handle snakes colliding with themselves and with each other
+        let dead: Vec<(usize, DeathCause)> = (0..self.snakes.len())
+            .filter(|&player| self.snakes[player].alive)
+            .filter_map(|player| Some((player, self.check_collision(player)?)))
+            .collect();
+        for (player, cause) in dead {
+            self.kill(player, cause);
+            outcomes[player] = Some(TickOutcome::Died(cause));
+        }
+
+        // Without a free cell for new food, the board is full
+        let mut board_full = false;
+        if food_eaten && self.alive_count() > 0 {
+            match GameInstance::generate_random_food(&self.grid.cells, &self.snakes, &mut self.rng)
+            {
+                Some(food) => self.food = food,
+                None => board_full = true,
+            }
         }
-        // Handle snake eating food
-        if self.snake.get_head() == &self.food {
-            // Add another segment to the snake by restoring his old tail segment
-            self.snake.restore_tail();
-            // Generate new food
-            self.food = GameInstance::generate_random_food(&self.grid.cells, &self.snake)
+        Tick {
+            outcomes,
+            board_full,
         }
-        true
     }
 
-    fn generate_random_food(cells: &[GridCell], snake: &Snake) -> GridCell {
-        let mut rng = rand::thread_rng();
+    // A random free cell, or `None` when the snakes fill the whole grid
+    fn generate_random_food(
+        cells: &[GridCell],
+        snakes: &[Snake],
+        rng: &mut StdRng,
+    ) -> Option<GridCell> {
         let empty_cells: Vec<GridCell> = cells
             .iter()
             .cloned()
-            .filter(|cell| !snake.body.contains(cell))
+            .filter(|cell| {
+                !snakes
+                    .iter()
+                    .any(|snake| snake.alive && snake.body.contains(cell))
+            })
             .collect();
+        if empty_cells.is_empty() {
+            return None;
+        }
         let random_index = rng.gen_range(0..empty_cells.len());
-        empty_cells[random_index]
+        Some(empty_cells[random_index])
     }
 
-    fn move_snake(&mut self) {
-        // Get current head
-        let head = self.snake.get_head();
-
+    fn move_snake(&mut self, player: usize) {
+        let snake = &mut self.snakes[player];
         // Create new head based on direction
-        let new_head = match self.direction {
-            // If snake is at an edge, wrap around to other side
-            Direction::Right => {
-                let x = if head.x == self.grid.x_max {
-                    self.grid.x_min
-                } else {
-                    head.x + 1
-                };
-                let y = head.y;
-                GridCell { x, y }
-            }
-            Direction::Left => {
-                let x = if head.x == self.grid.x_min {
-                    self.grid.x_max
-                } else {
-                    head.x - 1
-                };
-                let y = head.y;
-                GridCell { x, y }
-            }
-            Direction::Up => {
-                let x = head.x;
-                let y = if head.y == self.grid.y_min {
-                    self.grid.y_max
-                } else {
-                    head.y - 1
-                };
-                GridCell { x, y }
-            }
-            Direction::Down => {
-                let x = head.x;
-                let y = if head.y == self.grid.y_max {
-                    self.grid.y_min
-                } else {
-                    head.y + 1
-                };
-                GridCell { x, y }
-            }
-        };
+        let new_head = self.grid.next_cell(snake.get_head(), snake.direction);
 
         // Push new head to start of snake
-        self.snake.add_head(new_head);
+        snake.add_head(new_head);
 
         // Return old tail from snake
-        self.snake.remove_tail()
+        snake.remove_tail()
     }
 
-    fn check_collision(&mut self) -> bool {
-        let mut collision = false;
-        let head = self.snake.get_head();
+    // What the head of a snake ran into, if anything
+    fn check_collision(&self, player: usize) -> Option<DeathCause> {
+        let head = self.snakes[player].get_head();
 
-        for segment in self.snake.body.range(1..) {
-            if head == segment {
-                collision = true;
-                break;
-            }
+        self.snakes
+            .iter()
+            .enumerate()
+            .filter(|(_, snake)| snake.alive)
+            .find(|(other, snake)| {
+                // Hitting its own body, or any part of another snake including its head
+                let first = if *other == player { 1 } else { 0 };
+                snake.body.range(first..).any(|segment| segment == head)
+            })
+            .map(|(other, _)| match other == player {
+                true => DeathCause::OwnBody,
+                false => DeathCause::Snake(other),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Snake over `cells`, head first
+    fn snake(cells: &[(u16, u16)], direction: Direction) -> Snake {
+        Snake {
+            body: cells.iter().map(|&(x, y)| GridCell { x, y }).collect(),
+            old_tail: None,
+            direction,
+            alive: true,
+            food_eaten: 0,
+            death: None,
         }
-        collision
+    }
+
+    // A 10x5 grid with the given snakes and the food in a corner out of their way
+    fn instance(snakes: Vec<Snake>) -> GameInstance {
+        let mut instance = GameInstance::with_snakes(GameGrid::with_dimensions(10, 5), snakes, 0);
+        instance.food = GridCell { x: 9, y: 4 };
+        instance
+    }
+
+    fn died(cause: DeathCause) -> Option<TickOutcome> {
+        Some(TickOutcome::Died(cause))
+    }
+
+    #[test]
+    fn heads_meeting_on_a_cell_kill_both_snakes() {
+        let mut instance = instance(vec![
+            snake(&[(3, 1), (2, 1), (1, 1)], Direction::Right),
+            snake(&[(5, 1), (6, 1), (7, 1)], Direction::Left),
+        ]);
+        let tick = instance.game_cycle();
+        assert_eq!(
+            tick.outcomes,
+            vec![died(DeathCause::Snake(1)), died(DeathCause::Snake(0))]
+        );
+        assert_eq!(instance.alive_count(), 0);
+        let death = instance.snakes[0].death.unwrap();
+        assert_eq!((death.tick, death.cell), (1, GridCell { x: 4, y: 1 }));
+    }
+
+    #[test]
+    fn heads_swapping_cells_kill_both_snakes() {
+        let mut instance = instance(vec![
+            snake(&[(3, 1), (2, 1), (1, 1)], Direction::Right),
+            snake(&[(4, 1), (5, 1), (6, 1)], Direction::Left),
+        ]);
+        let tick = instance.game_cycle();
+        assert_eq!(
+            tick.outcomes,
+            vec![died(DeathCause::Snake(1)), died(DeathCause::Snake(0))]
+        );
+    }
+
+    #[test]
+    fn a_head_hitting_a_body_kills_only_that_snake() {
+        let mut instance = instance(vec![
+            snake(&[(4, 1), (4, 0), (3, 0)], Direction::Down),
+            snake(&[(6, 2), (5, 2), (4, 2), (3, 2)], Direction::Right),
+        ]);
+        let tick = instance.game_cycle();
+        assert_eq!(
+            tick.outcomes,
+            vec![died(DeathCause::Snake(1)), Some(TickOutcome::Moved)]
+        );
+        assert!(instance.snakes[1].alive);
+        assert_eq!(instance.alive_count(), 1);
+    }
+
+    #[test]
+    fn a_tail_vacated_in_the_same_tick_is_free() {
+        let mut instance = instance(vec![
+            snake(&[(4, 1), (4, 0), (3, 0)], Direction::Down),
+            snake(&[(6, 2), (5, 2), (4, 2)], Direction::Right),
+        ]);
+        let tick = instance.game_cycle();
+        assert_eq!(tick.outcomes, vec![Some(TickOutcome::Moved); 2]);
+        assert_eq!(instance.snakes[0].get_head(), &GridCell { x: 4, y: 2 });
+        assert_eq!(instance.snakes[1].old_tail, Some(GridCell { x: 4, y: 2 }));
+    }
+
+    #[test]
+    fn a_tail_kept_by_eating_is_not_free() {
+        let mut instance = instance(vec![
+            snake(&[(4, 1), (4, 0), (3, 0)], Direction::Down),
+            snake(&[(6, 2), (5, 2), (4, 2)], Direction::Right),
+        ]);
+        instance.food = GridCell { x: 7, y: 2 };
+        let tick = instance.game_cycle();
+        assert_eq!(
+            tick.outcomes,
+            vec![died(DeathCause::Snake(1)), Some(TickOutcome::AteFood)]
+        );
+        assert_eq!(instance.snakes[1].body.len(), 4);
+    }
+
+    #[test]
+    fn a_snake_can_follow_its_own_tail() {
+        let mut instance = instance(vec![snake(
+            &[(4, 1), (4, 2), (5, 2), (5, 1)],
+            Direction::Right,
+        )]);
+        let tick = instance.game_cycle();
+        assert_eq!(tick.outcomes, vec![Some(TickOutcome::Moved)]);
+        assert_eq!(instance.snakes[0].get_head(), &GridCell { x: 5, y: 1 });
     }
 }
@@ -1,173 +1,882 @@
-use std::io::{Stdout, Write};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::mem;
 
 use cfonts::{Align, Colors, Fonts, Options};
-use termion::raw::RawTerminal;
 use termion::{clear, color, cursor};
 
+use crate::error::GameError;
 use crate::game_instance::GridCell;
 use crate::game_instance::Snake;
+use crate::glyphs::{self, GlyphSet};
+use crate::parser::SnakeSkin;
+use crate::skin::{self, Segment};
+use crate::theme::{Color, ColorDepth, Theme};
 
-const FOOD_CHAR: char = '\u{00D3}';
+// Palette entries of the colours used for text
+const BLACK: Color = Color::Ansi(0);
+const YELLOW: Color = Color::Ansi(3);
+const WHITE: Color = Color::Ansi(7);
+const MENU_BACKGROUND: Color = Color::Ansi(236);
 
-// TODO: still need to figure out how to abstract this part properly
+// Dots of a braille character from its top-left, as (column, row) within the character
+const BRAILLE_DOTS: [(u16, u16); 8] = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (1, 0),
+    (1, 1),
+    (1, 2),
+    (0, 3),
+    (1, 3),
+];
+
+// How many grid cells share a terminal character
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Packing {
+    // One cell per character, or per `width` characters
+    Single,
+    // Two cells above each other, drawn with upper and lower half blocks
+    HalfBlock,
+    // Two columns of four cells, drawn as the dots of a braille character
+    Braille,
+}
+
+// Where grid cells appear on the screen. Cell (1, 1) is in the top-left corner of the screen.
+#[derive(Debug, Clone, Copy)]
+pub struct CellLayout {
+    packing: Packing,
+    // Columns taken by a cell that has a character to itself
+    width: u16,
+}
+
+impl CellLayout {
+    // Every cell in a character of its own, `width` columns wide
+    pub fn single(width: u16) -> Self {
+        Self {
+            packing: Packing::Single,
+            width: width.max(1),
+        }
+    }
+
+    pub fn half_block() -> Self {
+        Self {
+            packing: Packing::HalfBlock,
+            width: 1,
+        }
+    }
+
+    pub fn braille() -> Self {
+        Self {
+            packing: Packing::Braille,
+            width: 1,
+        }
+    }
+
+    // Cells sharing a character across and down
+    fn packed(&self) -> (u16, u16) {
+        match self.packing {
+            Packing::Single => (1, 1),
+            Packing::HalfBlock => (1, 2),
+            Packing::Braille => (2, 4),
+        }
+    }
+
+    // Number of cells that fit across `columns` and down `rows`
+    pub fn cells_in(&self, columns: u16, rows: u16) -> (u16, u16) {
+        let (across, down) = self.packed();
+        (columns / self.width * across, rows * down)
+    }
+
+    // Columns and rows needed for `width` by `height` cells
+    pub fn screen_size(&self, width: u16, height: u16) -> (u16, u16) {
+        let (across, down) = self.packed();
+        (width.div_ceil(across) * self.width, height.div_ceil(down))
+    }
+
+    // Height of a cell divided by its width, given that of a terminal character
+    pub fn aspect(&self, char_aspect: f64) -> f64 {
+        let (across, down) = self.packed();
+        char_aspect * f64::from(across) / f64::from(down * self.width)
+    }
+
+    // First and last screen column covered by the cells in columns `xmin` to `xmax`
+    pub fn columns(&self, xmin: u16, xmax: u16) -> (u16, u16) {
+        (self.column(xmin), self.column(xmax) + self.width - 1)
+    }
+
+    // First and last screen row covered by the cells in rows `ymin` to `ymax`
+    pub fn rows(&self, ymin: u16, ymax: u16) -> (u16, u16) {
+        (self.row(ymin), self.row(ymax))
+    }
+
+    fn column(&self, x: u16) -> u16 {
+        x.saturating_sub(1) / self.packed().0 * self.width + 1
+    }
+
+    pub fn row(&self, y: u16) -> u16 {
+        y.saturating_sub(1) / self.packed().1 + 1
+    }
+
+    // Top-left cell of the character showing cell (x, y)
+    fn origin(&self, x: u16, y: u16) -> (u16, u16) {
+        let (across, down) = self.packed();
+        (
+            x - x.saturating_sub(1) % across,
+            y - y.saturating_sub(1) % down,
+        )
+    }
+
+    // `glyph` padded with spaces to the width of a cell
+    fn fill(&self, glyph: char) -> String {
+        self.fill_with(glyph, ' ')
+    }
+
+    // `glyph` followed by as many `pad`s as fit in the rest of a cell
+    fn fill_with(&self, glyph: char, pad: char) -> String {
+        let pad = pad
+            .to_string()
+            .repeat(self.width.saturating_sub(glyphs::width(glyph)) as usize);
+        format!("{glyph}{pad}")
+    }
+
+    // Whether cells have room for double-width glyphs
+    fn wide(&self) -> bool {
+        self.width >= 2
+    }
+}
+
+impl Default for CellLayout {
+    fn default() -> Self {
+        Self::single(1)
+    }
+}
+
+// What a grid cell shows
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Paint {
+    Border,
+    Snake(Segment),
+    Food,
+    Fatal,
+}
+
+impl Paint {
+    // When cells share a character, the one with the highest priority gives it its colour
+    fn priority(&self) -> u8 {
+        match self {
+            Paint::Border => 0,
+            Paint::Snake(_) => 1,
+            Paint::Food => 2,
+            Paint::Fatal => 3,
+        }
+    }
+
+    fn color(&self, theme: &Theme) -> Color {
+        match self {
+            Paint::Border => theme.border,
+            Paint::Snake(segment) => segment.bg.unwrap_or(segment.fg),
+            Paint::Food => theme.food,
+            Paint::Fatal => theme.fatal,
+        }
+    }
+
+    // Glyph, the padding after it and their colours when the cell has a character to itself,
+    // which is two columns `wide` or one
+    fn glyph(
+        &self,
+        theme: &Theme,
+        glyphs: GlyphSet,
+        wide: bool,
+    ) -> (char, char, Color, Option<Color>) {
+        match self {
+            Paint::Border => (' ', ' ', theme.snake_glyph, Some(theme.border)),
+            Paint::Snake(segment) => (segment.glyph, segment.pad, segment.fg, segment.bg),
+            Paint::Food => (glyphs.food(wide), ' ', theme.food_glyph, Some(theme.food)),
+            Paint::Fatal => (
+                glyphs.fatal(wide),
+                ' ',
+                theme.fatal_glyph,
+                Some(theme.fatal),
+            ),
+        }
+    }
+}
+
+// What is drawn on each grid cell, so that a character can be composed from all the cells it
+// shows
+#[derive(Default)]
+struct FrameBuffer {
+    cells: HashMap<(u16, u16), Paint>,
+}
+
+impl FrameBuffer {
+    fn get(&self, x: u16, y: u16) -> Option<Paint> {
+        self.cells.get(&(x, y)).copied()
+    }
+
+    fn set(&mut self, x: u16, y: u16, paint: Option<Paint>) {
+        match paint {
+            Some(paint) => self.cells.insert((x, y), paint),
+            None => self.cells.remove(&(x, y)),
+        };
+    }
+
+    fn clear(&mut self) {
+        self.cells.clear();
+    }
+}
+
+// A character on the screen and its colours, `None` being the terminal's defaults
+#[derive(Debug, Clone, PartialEq)]
+struct Styled {
+    text: String,
+    fg: Option<Color>,
+    bg: Option<Color>,
+}
+
+impl Styled {
+    fn blank(layout: &CellLayout) -> Self {
+        Self {
+            text: layout.fill(' '),
+            fg: None,
+            bg: None,
+        }
+    }
+}
+
+// Writes to a terminal already in raw mode, such as the local alternate screen or a telnet socket
 pub struct GameOutput {
-    output: termion::screen::AlternateScreen<RawTerminal<Stdout>>,
+    output: Box<dyn Write + Send>,
+    layout: CellLayout,
+    theme: Theme,
+    depth: ColorDepth,
+    skin: SnakeSkin,
+    glyphs: GlyphSet,
+    frame: FrameBuffer,
+    // Board characters drawn since the last render, by row and column
+    back: BTreeMap<(u16, u16), Styled>,
+    // Board characters known to be on the screen, by row and column
+    front: HashMap<(u16, u16), Styled>,
 }
 
 impl GameOutput {
-    pub fn new(output: termion::screen::AlternateScreen<RawTerminal<Stdout>>) -> Self {
-        Self { output }
+    pub fn new(output: impl Write + Send + 'static) -> Self {
+        Self {
+            output: Box::new(output),
+            layout: CellLayout::default(),
+            theme: Theme::default(),
+            depth: ColorDepth::detect(),
+            skin: SnakeSkin::Classic,
+            glyphs: GlyphSet::detect(),
+            frame: FrameBuffer::default(),
+            back: BTreeMap::new(),
+            front: HashMap::new(),
+        }
+    }
+
+    // Draw grid cells and borders with `layout`. Text is placed in screen coordinates.
+    pub fn set_layout(&mut self, layout: CellLayout) {
+        self.layout = layout;
+    }
+
+    pub fn layout(&self) -> CellLayout {
+        self.layout
+    }
+
+    // Draw the board in the colours of `theme`, as close as `depth` allows
+    pub fn set_theme(&mut self, theme: Theme, depth: ColorDepth) {
+        self.theme = theme;
+        self.depth = depth;
     }
-    pub fn render(&mut self) {
-        self.output.flush().unwrap();
+
+    pub fn set_skin(&mut self, skin: SnakeSkin) {
+        self.skin = skin;
     }
 
-    pub fn clear_screen(&mut self) {
-        write!(self.output, "{}{}", clear::All, cursor::Hide).unwrap();
+    pub fn set_glyphs(&mut self, glyphs: GlyphSet) {
+        self.glyphs = glyphs;
+    }
+
+    // Colours for a cfonts banner, which has none without colour support
+    fn font_colors(&self, colors: Vec<Colors>) -> Vec<Colors> {
+        match self.depth {
+            ColorDepth::None => vec![Colors::System],
+            _ => colors,
+        }
     }
 
-    pub fn show_cursor(&mut self) {
-        write!(self.output, "{}{}", cursor::Goto(1, 1), cursor::Show).unwrap();
+    pub fn render(&mut self) -> Result<(), GameError> {
+        self.flush_board()?;
+        self.output.flush()?;
+        Ok(())
     }
 
-    pub fn draw_game_over_transition_msg(&mut self, min_y: u16, max_y: u16) {
+    pub fn clear_screen(&mut self) -> Result<(), GameError> {
+        self.frame.clear();
+        self.back.clear();
+        self.front.clear();
+        write!(self.output, "{}{}", clear::All, cursor::Hide)?;
+        Ok(())
+    }
+
+    pub fn show_cursor(&mut self) -> Result<(), GameError> {
+        self.overlay()?;
+        write!(self.output, "{}{}", cursor::Goto(1, 1), cursor::Show)?;
+        Ok(())
+    }
+
+    pub fn draw_game_over_transition_msg(
+        &mut self,
+        min_y: u16,
+        max_y: u16,
+    ) -> Result<(), GameError> {
+        self.overlay()?;
         let msg = cfonts::render(Options {
             text: String::from("game|over!"),
-            font: Fonts::FontBlock,
+            font: self.glyphs.font(Fonts::FontBlock),
             align: Align::Center,
             line_height: 0,
-            colors: vec![Colors::RedBright],
+            colors: self.font_colors(vec![Colors::RedBright]),
             spaceless: true,
             ..Options::default()
         });
         let msg = msg.text.replace('\n', "\r\n");
         let font_block_spacing = 5;
-        let height = ((min_y + max_y) / 2) - font_block_spacing;
-        write!(self.output, "{}{}", termion::cursor::Goto(1, height), msg).unwrap();
+        // Stay on screen when the board is too low for the message to sit above its middle
+        let height = ((min_y + max_y) / 2)
+            .saturating_sub(font_block_spacing)
+            .max(1);
+        write!(self.output, "{}{}", termion::cursor::Goto(1, height), msg)?;
+        Ok(())
     }
 
-    pub fn draw_game_over_message(&mut self, len: usize) {
-        let msg = cfonts::render(Options {
-            text: String::from("game|over!"),
-            font: Fonts::FontHuge,
-            align: Align::Center,
-            ..Options::default()
-        });
-        let msg = msg.text.replace('\n', "\r\n");
+    pub fn draw_game_over_message(
+        &mut self,
+        len: usize,
+        notes: &[String],
+    ) -> Result<(), GameError> {
         let prompt = format!(
-            "You reached a snake length of {len}! Would you like to play again?|Press 'p' to play again, press 'q' to quit."
+            "{}|You reached a snake length of {len}! Would you like to play again?|Press 'p' to play again, press 'q' to quit.",
+            notes.join("|")
         );
-        let msg2 = cfonts::render(Options {
-            text: prompt,
-            font: Fonts::FontConsole,
-            align: Align::Center,
-            ..Options::default()
-        });
-        let msg2 = msg2.text.replace('\n', "\r\n").to_uppercase();
-        write!(self.output, "{}", termion::cursor::Goto(1, 1)).unwrap();
-        write!(self.output, "{}{}{}", msg, msg2, color::Bg(color::Reset),).unwrap();
+        self.draw_banner("game|over!", Fonts::FontHuge, vec![Colors::System], &prompt)
     }
 
-    pub fn draw_pre_game_message(&mut self) {
-        let msg1 = cfonts::render(Options {
-            text: String::from("welcome to"),
-            font: Fonts::FontBlock,
+    pub fn draw_victory_message(&mut self, stats: &[String]) -> Result<(), GameError> {
+        let prompt = format!(
+            "{}|Press 'p' to play again, press 'q' to quit.",
+            stats.join("|")
+        );
+        self.draw_banner(
+            "you|win!",
+            Fonts::FontHuge,
+            vec![Colors::GreenBright],
+            &prompt,
+        )
+    }
+
+    pub fn draw_winner_message(
+        &mut self,
+        title: &str,
+        results: &[String],
+    ) -> Result<(), GameError> {
+        let prompt = format!(
+            "{}|Press 'p' to play again, press 'q' to quit.",
+            results.join("|")
+        );
+        self.draw_banner(title, Fonts::FontHuge, vec![Colors::System], &prompt)
+    }
+
+    pub fn draw_lobby_message(
+        &mut self,
+        player: usize,
+        players: usize,
+        connected: usize,
+        ready: usize,
+    ) -> Result<(), GameError> {
+        let prompt = format!(
+            "You are player {}.|Connected: {connected}/{players}, ready: {ready}/{players}.|\
+             Press 'p' when you are ready, press 'q' to quit.",
+            player + 1
+        );
+        self.draw_banner(
+            "lobby",
+            Fonts::FontBlock,
+            vec![Colors::Yellow, Colors::Candy],
+            &prompt,
+        )
+    }
+
+    pub fn draw_spectator_message(&mut self) -> Result<(), GameError> {
+        self.draw_banner(
+            "spectating",
+            Fonts::FontBlock,
+            vec![Colors::Yellow, Colors::Candy],
+            "Waiting for the next round to start.|Press 'q' to quit.",
+        )
+    }
+
+    // A centered title with centered lines of console text below it, '|' separating the lines
+    fn draw_banner(
+        &mut self,
+        title: &str,
+        font: Fonts,
+        colors: Vec<Colors>,
+        lines: &str,
+    ) -> Result<(), GameError> {
+        self.overlay()?;
+        let title = cfonts::render(Options {
+            text: String::from(title),
+            font: self.glyphs.font(font),
             align: Align::Center,
-            colors: vec![Colors::Yellow, Colors::Candy],
+            colors: self.font_colors(colors),
             ..Options::default()
         });
-        let msg1 = msg1.text.replace('\n', "\r\n");
-        let msg2 = cfonts::render(Options {
-            text: String::from("snake"),
-            font: Fonts::Font3d,
+        let title = title.text.replace('\n', "\r\n");
+        let lines = cfonts::render(Options {
+            text: String::from(lines),
+            font: Fonts::FontConsole,
             align: Align::Center,
-            colors: vec![Colors::Green, Colors::Gray],
             ..Options::default()
         });
-        let msg2 = msg2.text.replace('\n', "\r\n");
-        let prompt = "|||Press 'p' to play, press 'q' to quit.".to_string();
-        let msg3 = cfonts::render(Options {
-            text: prompt,
-            font: Fonts::FontConsole,
-            align: Align::Center,
+        let lines = lines.text.replace('\n', "\r\n").to_uppercase();
+        write!(self.output, "{}", termion::cursor::Goto(1, 1))?;
+        write!(self.output, "{}{}{}", title, lines, color::Bg(color::Reset))?;
+        Ok(())
+    }
+
+    // Plain text, since the terminal may be too small for any of the fonts
+    pub fn draw_too_small_message(&mut self, width: u16, height: u16) -> Result<(), GameError> {
+        self.overlay()?;
+        write!(
+            self.output,
+            "{}Terminal too small,\r\nplease enlarge it\r\nto {width}x{height}.\r\n\r\nPress 'q' to quit.",
+            cursor::Goto(1, 1)
+        )
+        ?;
+        Ok(())
+    }
+
+    // One line of text centred on row `y` of the board, cut to the board's width
+    pub fn draw_board_message(
+        &mut self,
+        xmin: u16,
+        xmax: u16,
+        y: u16,
+        text: &str,
+    ) -> Result<(), GameError> {
+        self.overlay()?;
+        let width = (xmax - xmin + 1) as usize;
+        let text: String = text.chars().take(width).collect();
+        let x = xmin + ((width - text.chars().count()) / 2) as u16;
+        write!(
+            self.output,
+            "{}{}{}{text}{}{}",
+            cursor::Goto(x, y),
+            self.depth.bg(WHITE),
+            self.depth.fg(BLACK),
+            color::Fg(color::Reset),
+            color::Bg(color::Reset),
+        )?;
+        Ok(())
+    }
+
+    // Menu in a box centred on (x, y), drawn over whatever is on the screen. The `selected`
+    // entry is highlighted.
+    pub fn draw_menu(
+        &mut self,
+        x: u16,
+        y: u16,
+        title: &str,
+        entries: &[String],
+        selected: usize,
+    ) -> Result<(), GameError> {
+        self.overlay()?;
+        let title = cfonts::render(Options {
+            text: String::from(title),
+            font: self.glyphs.font(Fonts::FontTiny),
+            align: Align::Left,
+            spaceless: true,
             ..Options::default()
         });
-        let msg3 = msg3.text.replace('\n', "\r\n").to_uppercase();
-        write!(self.output, "{}", termion::cursor::Goto(1, 1)).unwrap();
-        write!(self.output, "{}{}{}", msg1, msg2, msg3).unwrap();
+        let title: Vec<&str> = title.text.lines().collect();
+        let width = title
+            .iter()
+            .map(|line| line.chars().count())
+            .chain(entries.iter().map(|entry| entry.chars().count() + 4))
+            .max()
+            .unwrap_or(0);
+        let [top_left, top_right, bottom_right, bottom_left] = self.glyphs.corners();
+        let (line, side) = (self.glyphs.horizontal(), self.glyphs.vertical());
+        let edge = line.to_string().repeat(width + 2);
+        let mut rows = vec![format!("{top_left}{edge}{top_right}")];
+        for line in title {
+            rows.push(format!(
+                "{side} {}{line:^width$}{} {side}",
+                self.depth.fg(YELLOW),
+                color::Fg(color::Reset)
+            ));
+        }
+        rows.push(format!("{side} {} {side}", " ".repeat(width)));
+        let background = self.depth.bg(MENU_BACKGROUND);
+        for (index, entry) in entries.iter().enumerate() {
+            if index == selected {
+                let entry = format!("> {entry} <");
+                rows.push(format!(
+                    "{side} {}{}{entry:^width$}{}{background} {side}",
+                    self.depth.bg(YELLOW),
+                    self.depth.fg(BLACK),
+                    color::Fg(color::Reset),
+                ));
+            } else {
+                rows.push(format!("{side} {entry:^width$} {side}"));
+            }
+        }
+        rows.push(format!("{bottom_left}{edge}{bottom_right}"));
+
+        let left = x.saturating_sub(width as u16 / 2 + 2).max(1);
+        let top = y.saturating_sub(rows.len() as u16 / 2).max(1);
+        for (row, text) in (top..).zip(rows) {
+            write!(self.output, "{}{background}{text}", cursor::Goto(left, row))?;
+        }
+        write!(self.output, "{}", color::Bg(color::Reset))?;
+        Ok(())
+    }
+
+    // Number of a countdown in large digits, centred on the board. A board too small for them
+    // gets small ones.
+    pub fn draw_countdown(
+        &mut self,
+        xmin: u16,
+        xmax: u16,
+        ymin: u16,
+        ymax: u16,
+        count: u32,
+    ) -> Result<(), GameError> {
+        self.overlay()?;
+        let (width, height) = ((xmax - xmin + 1) as usize, (ymax - ymin + 1) as usize);
+        let glyphs = self.glyphs;
+        let render = |font| {
+            cfonts::render(Options {
+                text: count.to_string(),
+                font: glyphs.font(font),
+                align: Align::Left,
+                spaceless: true,
+                ..Options::default()
+            })
+            .vec
+        };
+        let mut lines = render(Fonts::FontBlock);
+        let fits = |lines: &[String]| {
+            lines.len() <= height && lines.iter().all(|line| line.chars().count() <= width)
+        };
+        if !fits(&lines) {
+            lines = render(Fonts::FontTiny);
+        }
+        if !fits(&lines) {
+            lines = vec![count.to_string()];
+        }
+        let line_width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        let x = xmin + ((width - line_width) / 2) as u16;
+        let y = ymin + ((height - lines.len()) / 2) as u16;
+        write!(self.output, "{}", self.depth.fg(YELLOW))?;
+        for (row, line) in (y..).zip(&lines) {
+            write!(self.output, "{}{line}", cursor::Goto(x, row))?;
+        }
+        write!(self.output, "{}", color::Fg(color::Reset))?;
+        Ok(())
     }
 
-    pub fn draw_border(&mut self, xmin: u16, xmax: u16, ymin: u16, ymax: u16) {
+    pub fn draw_border(
+        &mut self,
+        xmin: u16,
+        xmax: u16,
+        ymin: u16,
+        ymax: u16,
+    ) -> Result<(), GameError> {
         for i in xmin - 1..=xmax + 1 {
             for j in ymin - 1..=ymax + 1 {
-                match i {
-                    n if (n == xmin - 1 || n == xmax + 1) => write!(
-                        self.output,
-                        "{goto}{bgColor} ",
-                        goto = cursor::Goto(i, j),
-                        bgColor = color::Bg(color::White),
-                    )
-                    .unwrap(),
-                    _ => (),
-                };
-                match j {
-                    n if (n == ymin - 1 || n == ymax + 1) => write!(
-                        self.output,
-                        "{goto}{bgColor} ",
-                        goto = cursor::Goto(i, j),
-                        bgColor = color::Bg(color::White),
-                    )
-                    .unwrap(),
-                    _ => (),
-                };
+                if i == xmin - 1 || i == xmax + 1 || j == ymin - 1 || j == ymax + 1 {
+                    self.draw_cell(i, j, Some(Paint::Border))?;
+                }
             }
         }
-        write!(self.output, "{}", color::Bg(color::Reset),).unwrap()
+        Ok(())
     }
 
-    pub fn draw_food(&mut self, food: &GridCell) {
-        write!(
-            self.output,
-            "{goto}{bgColor}{fgColor}{food_char}{fgreset}{bgreset}",
-            goto = cursor::Goto(food.x, food.y),
-            bgColor = color::Bg(color::Red),
-            fgColor = color::Fg(color::LightGreen),
-            food_char = FOOD_CHAR,
-            fgreset = color::Fg(color::Reset),
-            bgreset = color::Bg(color::Reset),
-        )
-        .unwrap();
-    }
-
-    pub fn draw_snake(&mut self, snake: &Snake) {
-        let body = &snake.body;
-        let len = body.len();
-        for (seg_num, segment) in body.iter().enumerate() {
-            let segment_char = match seg_num {
-                0 => 'S',
-                num if num == len - 1 => 'e',
-                1 => 'n',
-                num if num == len - 2 => 'k',
-                _ => 'a',
-            };
-            write!(
-                self.output,
-                "{goto}{fgColor}{bgColor}{segment_char}{reset}",
-                goto = cursor::Goto(segment.x, segment.y),
-                fgColor = color::Fg(color::Black),
-                bgColor = color::Bg(color::Green),
-                segment_char = segment_char,
-                reset = color::Bg(color::Reset),
-            )
-            .unwrap();
-        }
-        let tail = &snake.old_tail;
-        if let Some(x) = tail {
-            self.undraw(x)
-        }
-    }
-
-    fn undraw(&mut self, cell: &GridCell) {
-        write!(self.output, "{} ", cursor::Goto(cell.x, cell.y)).unwrap();
+    pub fn draw_food(&mut self, food: &GridCell) -> Result<(), GameError> {
+        self.draw_cell(food.x, food.y, Some(Paint::Food))
+    }
+
+    // Draw the given snakes with their player numbers. All old tails are cleared before any
+    // body is drawn, so a tail one snake left behind never erases a head that moved onto it.
+    pub fn draw_snakes<'a>(
+        &mut self,
+        snakes: impl IntoIterator<Item = (usize, &'a Snake)>,
+    ) -> Result<(), GameError> {
+        let snakes: Vec<_> = snakes.into_iter().collect();
+        for (_, snake) in &snakes {
+            if let Some(tail) = &snake.old_tail {
+                self.undraw(tail)?;
+            }
+        }
+        for (player, snake) in snakes {
+            let segments = skin::segments(self.skin, &self.theme, self.glyphs, snake, player);
+            for (cell, segment) in snake.body.iter().zip(segments) {
+                self.draw_cell(cell.x, cell.y, Some(Paint::Snake(segment)))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn draw_fatal_cell(&mut self, cell: &GridCell) -> Result<(), GameError> {
+        self.draw_cell(cell.x, cell.y, Some(Paint::Fatal))
+    }
+
+    pub fn undraw_snake(&mut self, snake: &Snake) -> Result<(), GameError> {
+        for segment in &snake.body {
+            self.undraw(segment)?;
+        }
+        Ok(())
+    }
+
+    fn undraw(&mut self, cell: &GridCell) -> Result<(), GameError> {
+        self.draw_cell(cell.x, cell.y, None)
+    }
+
+    // Change what cell (x, y) shows and queue the character it is part of for the next render
+    fn draw_cell(&mut self, x: u16, y: u16, paint: Option<Paint>) -> Result<(), GameError> {
+        self.frame.set(x, y, paint);
+        let (x, y) = self.layout.origin(x, y);
+        let position = (self.layout.row(y), self.layout.column(x));
+        self.back.insert(position, self.compose(x, y));
+        Ok(())
+    }
+
+    // Character showing the cells from (x, y), the top-left one, with its colours. Without
+    // colours, cells that only have a background get a glyph instead.
+    fn compose(&self, x: u16, y: u16) -> Styled {
+        let paint = |dx, dy| self.frame.get(x + dx, y + dy);
+        let colored = self.depth != ColorDepth::None;
+        let theme = &self.theme;
+        let styled = |glyph: char, fg: Option<Color>, bg: Option<Color>| Styled {
+            text: glyph.to_string(),
+            fg: fg.filter(|_| colored),
+            bg: bg.filter(|_| colored),
+        };
+        match self.layout.packing {
+            Packing::Single => match paint(0, 0) {
+                Some(cell) => {
+                    let (glyph, pad, fg, bg) = cell.glyph(theme, self.glyphs, self.layout.wide());
+                    let (glyph, pad) = if glyph == ' ' && bg.is_some() && !colored {
+                        ('#', '#')
+                    } else {
+                        (glyph, pad)
+                    };
+                    Styled {
+                        text: self.layout.fill_with(glyph, pad),
+                        ..styled(glyph, Some(fg), bg)
+                    }
+                }
+                None => Styled::blank(&self.layout),
+            },
+            Packing::HalfBlock => match (paint(0, 0), paint(0, 1)) {
+                (Some(_), Some(_)) if !colored => styled('\u{2588}', None, None),
+                (Some(top), Some(bottom)) => styled(
+                    '\u{2580}',
+                    Some(top.color(theme)),
+                    Some(bottom.color(theme)),
+                ),
+                (Some(top), None) => styled('\u{2580}', Some(top.color(theme)), None),
+                (None, Some(bottom)) => styled('\u{2584}', Some(bottom.color(theme)), None),
+                (None, None) => Styled::blank(&self.layout),
+            },
+            Packing::Braille => {
+                let mut dots = 0;
+                let mut top = None;
+                for (bit, &(dx, dy)) in BRAILLE_DOTS.iter().enumerate() {
+                    if let Some(cell) = paint(dx, dy) {
+                        dots |= 1 << bit;
+                        if top.is_none_or(|top: Paint| cell.priority() > top.priority()) {
+                            top = Some(cell);
+                        }
+                    }
+                }
+                match top {
+                    Some(cell) => {
+                        let glyph = char::from_u32(0x2800 + dots).unwrap_or(' ');
+                        styled(glyph, Some(cell.color(theme)), None)
+                    }
+                    None => Styled::blank(&self.layout),
+                }
+            }
+        }
+    }
+
+    // Write the board characters that differ from what is on the screen, in screen order. The
+    // cursor is only moved and colours only changed where the previous character leaves them
+    // wrong.
+    fn flush_board(&mut self) -> Result<(), GameError> {
+        let mut at = None;
+        // Unknown until the first change
+        let (mut fg, mut bg) = (None, None);
+        for ((row, column), styled) in mem::take(&mut self.back) {
+            if self.front.get(&(row, column)) == Some(&styled) {
+                continue;
+            }
+            if at != Some((column, row)) {
+                write!(self.output, "{}", cursor::Goto(column, row))?;
+            }
+            if fg != Some(styled.fg) {
+                match styled.fg {
+                    Some(value) => write!(self.output, "{}", self.depth.fg(value))?,
+                    None => write!(self.output, "{}", color::Fg(color::Reset))?,
+                }
+                fg = Some(styled.fg);
+            }
+            if bg != Some(styled.bg) {
+                match styled.bg {
+                    Some(value) => write!(self.output, "{}", self.depth.bg(value))?,
+                    None => write!(self.output, "{}", color::Bg(color::Reset))?,
+                }
+                bg = Some(styled.bg);
+            }
+            write!(self.output, "{}", styled.text)?;
+            at = Some((column + self.layout.width, row));
+            self.front.insert((row, column), styled);
+        }
+        if let Some(Some(_)) = fg {
+            write!(self.output, "{}", color::Fg(color::Reset))?;
+        }
+        if let Some(Some(_)) = bg {
+            write!(self.output, "{}", color::Bg(color::Reset))?;
+        }
+        Ok(())
+    }
+
+    // Put the board on the screen before text is drawn over it. The text may cover any of its
+    // characters, so they are no longer known to be on the screen.
+    fn overlay(&mut self) -> Result<(), GameError> {
+        self.flush_board()?;
+        self.front.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    // Output the test can look at while the game output owns it
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedOutput {
+        fn take(&self) -> String {
+            String::from_utf8(mem::take(&mut self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    fn game_output() -> (GameOutput, SharedOutput) {
+        let shared = SharedOutput::default();
+        let mut output = GameOutput::new(shared.clone());
+        output.set_theme(Theme::default(), ColorDepth::Ansi256);
+        output.set_glyphs(GlyphSet::Ascii);
+        (output, shared)
+    }
+
+    #[test]
+    fn square_cells_take_a_character_each() {
+        let layout = CellLayout::single(2);
+        assert_eq!((layout.column(1), layout.column(3)), (1, 5));
+        assert_eq!((layout.row(1), layout.row(3)), (1, 3));
+        assert_eq!(layout.columns(3, 5), (5, 10));
+        assert_eq!(layout.origin(4, 7), (4, 7));
+        assert_eq!(layout.cells_in(81, 24), (40, 24));
+        assert_eq!(layout.screen_size(40, 24), (80, 24));
+    }
+
+    #[test]
+    fn half_blocks_share_a_character_between_two_rows() {
+        let layout = CellLayout::half_block();
+        assert_eq!((layout.column(1), layout.column(3)), (1, 3));
+        assert_eq!((layout.row(1), layout.row(2), layout.row(3)), (1, 1, 2));
+        assert_eq!(layout.origin(4, 7), (4, 7));
+        assert_eq!(layout.origin(4, 8), (4, 7));
+        assert_eq!(layout.cells_in(80, 24), (80, 48));
+        assert_eq!(layout.screen_size(80, 47), (80, 24));
+    }
+
+    #[test]
+    fn braille_shares_a_character_between_two_columns_and_four_rows() {
+        let layout = CellLayout::braille();
+        let columns = [1, 2, 3].map(|x| layout.column(x));
+        assert_eq!(columns, [1, 1, 2]);
+        let rows = [1, 4, 5, 8, 9].map(|y| layout.row(y));
+        assert_eq!(rows, [1, 1, 2, 2, 3]);
+        assert_eq!(layout.origin(4, 7), (3, 5));
+        assert_eq!(layout.cells_in(80, 24), (160, 96));
+        assert_eq!(layout.screen_size(161, 96), (81, 24));
+    }
+
+    #[test]
+    fn unchanged_characters_are_not_written_again() {
+        let (mut output, shared) = game_output();
+        output.draw_cell(2, 2, Some(Paint::Food)).unwrap();
+        output.draw_cell(5, 2, Some(Paint::Border)).unwrap();
+        output.render().unwrap();
+        assert!(!shared.take().is_empty());
+
+        output.draw_cell(2, 2, Some(Paint::Food)).unwrap();
+        output.render().unwrap();
+        assert_eq!(shared.take(), "");
+
+        output.draw_cell(2, 2, Some(Paint::Food)).unwrap();
+        output.draw_cell(5, 2, None).unwrap();
+        output.render().unwrap();
+        let written = shared.take();
+        assert!(written.starts_with(&cursor::Goto(5, 2).to_string()));
+        assert!(!written.contains(&cursor::Goto(2, 2).to_string()));
+    }
+
+    #[test]
+    fn colours_are_only_sent_when_they_change() {
+        let (mut output, shared) = game_output();
+        let theme = Theme::default();
+        for x in 2..=4 {
+            output.draw_cell(x, 2, Some(Paint::Food)).unwrap();
+        }
+        output.draw_cell(5, 2, Some(Paint::Border)).unwrap();
+        output.render().unwrap();
+        let written = shared.take();
+
+        // The characters follow each other, so the cursor is only moved to the first
+        assert!(written.starts_with(&cursor::Goto(2, 2).to_string()));
+        for x in 3..=5 {
+            assert!(!written.contains(&cursor::Goto(x, 2).to_string()));
+        }
+        let depth = ColorDepth::Ansi256;
+        assert_eq!(written.matches(&depth.fg(theme.food_glyph)).count(), 1);
+        assert_eq!(written.matches(&depth.bg(theme.food)).count(), 1);
+        assert_eq!(written.matches(&depth.bg(theme.border)).count(), 1);
+        assert!(written.ends_with(&color::Bg(color::Reset).to_string()));
     }
 }
@@ -0,0 +1,206 @@
+use std::env;
+
+use cfonts::Fonts;
+
+use crate::game_instance::Direction;
+
+// Characters the terminal is trusted to show
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GlyphSet {
+    Ascii,
+    Unicode,
+    // Unicode with emoji for the food and crashes, where cells are two columns wide
+    Emoji,
+}
+
+impl GlyphSet {
+    // Read from the environment: the Linux console and locales without UTF-8 get ASCII
+    pub fn detect() -> Self {
+        Self::detect_from(|name| env::var(name).ok())
+    }
+
+    // Detect from the environment variables `var` looks up
+    fn detect_from(var: impl Fn(&str) -> Option<String>) -> Self {
+        if var("TERM").is_some_and(|term| term == "linux") {
+            return GlyphSet::Ascii;
+        }
+        // The first of these that is set decides, as for every other program
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|name| var(name))
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+        if locale.contains("utf-8") || locale.contains("utf8") {
+            GlyphSet::Unicode
+        } else {
+            GlyphSet::Ascii
+        }
+    }
+
+    // Glyph of the food, in a cell two columns `wide` or one
+    pub fn food(&self, wide: bool) -> char {
+        match self {
+            GlyphSet::Ascii => '@',
+            GlyphSet::Emoji if wide => '\u{1F34E}',
+            _ => '\u{00D3}',
+        }
+    }
+
+    // Glyph marking where a snake crashed
+    pub fn fatal(&self, wide: bool) -> char {
+        match self {
+            GlyphSet::Emoji if wide => '\u{1F4A5}',
+            _ => 'X',
+        }
+    }
+
+    // Head of a snake moving in `direction`
+    pub fn arrow(&self, direction: Direction) -> char {
+        match (self, direction) {
+            (GlyphSet::Ascii, Direction::Up) => '^',
+            (GlyphSet::Ascii, Direction::Down) => 'v',
+            (GlyphSet::Ascii, Direction::Left) => '<',
+            (GlyphSet::Ascii, Direction::Right) => '>',
+            (_, Direction::Up) => '\u{25B2}',
+            (_, Direction::Down) => '\u{25BC}',
+            (_, Direction::Left) => '\u{25C0}',
+            (_, Direction::Right) => '\u{25B6}',
+        }
+    }
+
+    // Line joining the sides `a` and `b` of a cell. With one side, the line only reaches
+    // halfway.
+    pub fn pipe(&self, a: Option<Direction>, b: Option<Direction>) -> char {
+        use Direction::{Down, Left, Right, Up};
+        let ascii = *self == GlyphSet::Ascii;
+        match (a, b) {
+            (Some(Left), Some(Right)) | (Some(Right), Some(Left)) => self.horizontal(),
+            (Some(Up), Some(Down)) | (Some(Down), Some(Up)) => self.vertical(),
+            _ if ascii && a.is_some() && b.is_some() => '+',
+            (Some(Down), Some(Right)) | (Some(Right), Some(Down)) => '\u{250C}',
+            (Some(Down), Some(Left)) | (Some(Left), Some(Down)) => '\u{2510}',
+            (Some(Up), Some(Right)) | (Some(Right), Some(Up)) => '\u{2514}',
+            (Some(Up), Some(Left)) | (Some(Left), Some(Up)) => '\u{2518}',
+            (Some(Left | Right), _) | (_, Some(Left | Right)) if ascii => '-',
+            (Some(Up | Down), _) | (_, Some(Up | Down)) if ascii => '|',
+            (Some(Left), _) | (_, Some(Left)) => '\u{2574}',
+            (Some(Up), _) | (_, Some(Up)) => '\u{2575}',
+            (Some(Right), _) | (_, Some(Right)) => '\u{2576}',
+            (Some(Down), _) | (_, Some(Down)) => '\u{2577}',
+            (None, None) if ascii => 'o',
+            (None, None) => '\u{25CF}',
+        }
+    }
+
+    pub fn horizontal(&self) -> char {
+        match self {
+            GlyphSet::Ascii => '-',
+            _ => '\u{2500}',
+        }
+    }
+
+    pub fn vertical(&self) -> char {
+        match self {
+            GlyphSet::Ascii => '|',
+            _ => '\u{2502}',
+        }
+    }
+
+    // Glyphs of the last segments of a tapered snake, ending with the tail
+    pub fn taper(&self) -> [char; 3] {
+        match self {
+            GlyphSet::Ascii => ['=', '-', '.'],
+            _ => ['\u{2593}', '\u{2592}', '\u{2591}'],
+        }
+    }
+
+    // Corners of a box, clockwise from the top left
+    pub fn corners(&self) -> [char; 4] {
+        match self {
+            GlyphSet::Ascii => ['+'; 4],
+            _ => ['\u{250C}', '\u{2510}', '\u{2518}', '\u{2514}'],
+        }
+    }
+
+    // `font`, or the nearest cfonts font drawn with ASCII characters only
+    pub fn font(&self, font: Fonts) -> Fonts {
+        if *self != GlyphSet::Ascii {
+            return font;
+        }
+        match font {
+            Fonts::FontBlock => Fonts::FontSimple,
+            Fonts::FontHuge => Fonts::FontSimple3d,
+            Fonts::FontTiny => Fonts::FontConsole,
+            font => font,
+        }
+    }
+}
+
+// Number of columns a terminal gives `glyph`: two for emoji and East Asian wide characters
+pub fn width(glyph: char) -> u16 {
+    match glyph as u32 {
+        0x1100..=0x115F
+        | 0x2E80..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F680..=0x1F6FF
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Glyph set detected with only the environment variables in `vars` set
+    fn detect(vars: &[(&str, &str)]) -> GlyphSet {
+        GlyphSet::detect_from(|name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn detects_unicode_from_the_locale() {
+        assert_eq!(detect(&[]), GlyphSet::Ascii);
+        assert_eq!(detect(&[("LANG", "en_US.UTF-8")]), GlyphSet::Unicode);
+        assert_eq!(detect(&[("LANG", "de_DE.utf8")]), GlyphSet::Unicode);
+        assert_eq!(detect(&[("LANG", "C")]), GlyphSet::Ascii);
+        // The Linux console has no glyphs beyond its font, whatever the locale
+        let console = [("TERM", "linux"), ("LANG", "en_US.UTF-8")];
+        assert_eq!(detect(&console), GlyphSet::Ascii);
+    }
+
+    #[test]
+    fn the_first_locale_variable_set_decides() {
+        let all = [("LC_ALL", "C"), ("LC_CTYPE", "en_US.UTF-8")];
+        assert_eq!(detect(&all), GlyphSet::Ascii);
+        let ctype = [("LC_CTYPE", "en_US.UTF-8"), ("LANG", "C")];
+        assert_eq!(detect(&ctype), GlyphSet::Unicode);
+        // An empty variable counts as unset
+        let empty = [("LC_ALL", ""), ("LANG", "en_US.UTF-8")];
+        assert_eq!(detect(&empty), GlyphSet::Unicode);
+    }
+
+    #[test]
+    fn emoji_and_wide_characters_take_two_columns() {
+        assert_eq!(width('a'), 1);
+        assert_eq!(width('\u{2580}'), 1);
+        assert_eq!(width('\u{25B6}'), 1);
+        assert_eq!(width('\u{1F34E}'), 2);
+        assert_eq!(width('\u{1F4A5}'), 2);
+        assert_eq!(width('\u{4E2D}'), 2);
+        // Emoji are only used where cells have room for them
+        assert_eq!(width(GlyphSet::Emoji.food(false)), 1);
+        assert_eq!(width(GlyphSet::Emoji.food(true)), 2);
+        assert_eq!(width(GlyphSet::Emoji.fatal(false)), 1);
+    }
+}
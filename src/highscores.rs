@@ -0,0 +1,178 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use crate::settings::config_dir;
+
+// Number of results kept in the table
+const MAX_SCORES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Score {
+    pub length: usize,
+    pub food: usize,
+    pub seconds: u64,
+    // The snake filled the whole board
+    pub perfect: bool,
+}
+
+impl Score {
+    fn to_json(&self) -> Value {
+        json!({
+            "length": self.length,
+            "food": self.food,
+            "seconds": self.seconds,
+            "perfect": self.perfect,
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        Some(Self {
+            length: value["length"].as_u64()? as usize,
+            food: value["food"].as_u64()? as usize,
+            seconds: value["seconds"].as_u64()?,
+            perfect: value["perfect"].as_bool().unwrap_or(false),
+        })
+    }
+}
+
+// Best single-player results, best first, stored as JSON in the user's config directory
+pub struct HighScores {
+    path: PathBuf,
+    scores: Vec<Score>,
+}
+
+impl HighScores {
+    // Load the table from the config directory. A missing or unreadable file starts an empty
+    // table. Returns `None` without a home directory.
+    pub fn load() -> Option<Self> {
+        Some(Self::load_from(config_dir()?.join("highscores.json")))
+    }
+
+    fn load_from(path: PathBuf) -> Self {
+        let scores = fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+            .and_then(|value| {
+                let scores = value.as_array()?.iter().filter_map(Score::from_json);
+                Some(scores.collect())
+            })
+            .unwrap_or_default();
+        Self { path, scores }
+    }
+
+    pub fn scores(&self) -> &[Score] {
+        &self.scores
+    }
+
+    pub fn best(&self) -> Option<&Score> {
+        self.scores.first()
+    }
+
+    // Add `score` to the table and save it. Returns its rank counting from 0, or `None` if it
+    // did not make the table.
+    pub fn record(&mut self, score: Score) -> io::Result<Option<usize>> {
+        // Longer snakes rank first, then perfect games, then faster ones
+        let rank = self
+            .scores
+            .iter()
+            .position(|other| {
+                (score.length, score.perfect, other.seconds)
+                    > (other.length, other.perfect, score.seconds)
+            })
+            .unwrap_or(self.scores.len());
+        if rank >= MAX_SCORES {
+            return Ok(None);
+        }
+        self.scores.insert(rank, score);
+        self.scores.truncate(MAX_SCORES);
+        self.save()?;
+        Ok(Some(rank))
+    }
+
+    fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let scores: Vec<Value> = self.scores.iter().map(Score::to_json).collect();
+        fs::write(&self.path, Value::Array(scores).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn score(length: usize, perfect: bool, seconds: u64) -> Score {
+        Score {
+            length,
+            food: length,
+            seconds,
+            perfect,
+        }
+    }
+
+    // A table stored under a file of its own, so tests running in parallel don't share one
+    fn table(name: &str) -> HighScores {
+        let path = env::temp_dir().join(format!("rs_snake-{name}-{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+        HighScores::load_from(path)
+    }
+
+    #[test]
+    fn ranks_length_then_perfect_then_time() {
+        let mut scores = table("ranks");
+        scores.record(score(5, false, 10)).unwrap();
+        scores.record(score(8, false, 50)).unwrap();
+        scores.record(score(5, true, 30)).unwrap();
+        scores.record(score(5, false, 5)).unwrap();
+
+        let expected = [
+            score(8, false, 50),
+            score(5, true, 30),
+            score(5, false, 5),
+            score(5, false, 10),
+        ];
+        assert_eq!(scores.scores(), &expected);
+        assert_eq!(scores.best(), Some(&expected[0]));
+        let _ = fs::remove_file(&scores.path);
+    }
+
+    #[test]
+    fn ties_rank_below_earlier_results() {
+        let mut scores = table("ties");
+        assert_eq!(scores.record(score(5, false, 10)).unwrap(), Some(0));
+        assert_eq!(scores.record(score(5, false, 10)).unwrap(), Some(1));
+        assert_eq!(scores.record(score(3, false, 10)).unwrap(), Some(2));
+        assert_eq!(scores.record(score(5, false, 10)).unwrap(), Some(2));
+        let _ = fs::remove_file(&scores.path);
+    }
+
+    #[test]
+    fn keeps_only_the_best_results_and_saves_them() {
+        let mut scores = table("trim");
+        for length in 1..=MAX_SCORES {
+            scores.record(score(length, false, 10)).unwrap();
+        }
+        assert_eq!(scores.scores().len(), MAX_SCORES);
+
+        // No better than the last entry, so it misses the table
+        assert_eq!(scores.record(score(1, false, 10)).unwrap(), None);
+        assert_eq!(scores.scores()[MAX_SCORES - 1], score(1, false, 10));
+
+        assert_eq!(
+            scores.record(score(4, false, 1)).unwrap(),
+            Some(MAX_SCORES - 4)
+        );
+        assert_eq!(scores.scores().len(), MAX_SCORES);
+        assert_eq!(scores.scores()[MAX_SCORES - 1], score(2, false, 10));
+
+        let loaded = HighScores::load_from(scores.path.clone());
+        assert_eq!(loaded.scores(), scores.scores());
+        let _ = fs::remove_file(&scores.path);
+    }
+}
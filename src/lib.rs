@@ -1 +1,27 @@
 extern crate termion;
+
+pub mod ai;
+pub mod arena;
+pub mod battlesnake;
+pub mod crash;
+pub mod env;
+pub mod error;
+pub mod ffi;
+pub mod game;
+pub mod game_input;
+pub mod game_instance;
+pub mod game_output;
+pub mod glyphs;
+pub mod highscores;
+pub mod net_client;
+pub mod net_protocol;
+pub mod net_server;
+pub mod parser;
+pub mod remote_game;
+pub mod settings;
+pub mod signals;
+pub mod skin;
+pub mod spectator;
+pub mod telnet;
+pub mod theme;
+pub mod ticker;
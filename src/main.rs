@@ -1,34 +1,166 @@
 use std::error::Error;
-use std::io::stdout;
+use std::io::{stdin, stdout};
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::time::Duration;
 
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use termion::input::TermRead;
 use termion::raw::IntoRawMode;
 use termion::screen::IntoAlternateScreen;
-use termion::{async_stdin, terminal_size};
+use termion::terminal_size;
 
-mod game;
-mod game_input;
-mod game_instance;
-mod game_output;
-mod parser;
+use rs_snake::glyphs::GlyphSet;
+use rs_snake::highscores::HighScores;
+use rs_snake::parser::Command;
+use rs_snake::settings::Settings;
+use rs_snake::theme::{ColorDepth, Theme};
+use rs_snake::{
+    arena, crash, game, game_input, game_output, net_client, net_server, parser, signals,
+    spectator, telnet,
+};
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {err}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
     // Parse arguments from command line
-    let args = parser::ArgsParser::parse();
+    let matches = parser::ArgsParser::command().get_matches();
+    let mut args = parser::ArgsParser::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if let Some(command) = args.command.clone() {
+        return run_command(command, &args);
+    }
+    // Options not given on the command line default to the choices made in the menus last time
+    let settings = Settings::load();
+    if let Some(settings) = &settings {
+        settings.apply(&mut args, &matches);
+    }
+    // Check the terminal and bind the spectator socket before taking over the terminal, so
+    // errors are readable
+    let terminal_size = terminal_size()?;
+    game::check_terminal_size(&args, terminal_size)?;
+    let theme = Theme::load(&args.theme)?;
+    let depth = args.color.depth().unwrap_or_else(ColorDepth::detect);
+    let glyphs = args.glyphs.glyphs().unwrap_or_else(GlyphSet::detect);
+    let spectators = match &args.spectate {
+        Some(addr) => Some(spectator::SpectatorServer::bind(addr)?),
+        None => None,
+    };
+
+    // Restore the terminal even if the game panics or gets killed
+    crash::install()?;
+
     // Initialize input handler
-    let input = async_stdin().keys();
-    let input = game_input::GameInput::new(input, args.movement_key_scheme);
+    let input = stdin().keys();
+    let input = game_input::GameInput::new(input, &args.key_schemes());
     // Initialize output handler
     let output = stdout().into_raw_mode()?.into_alternate_screen()?;
-    let output = game_output::GameOutput::new(output);
-
-    // Initialize rest of variables needed to initialize Game struct
-    let terminal_size = terminal_size()?;
+    let mut output = game_output::GameOutput::new(output);
+    output.set_theme(theme, depth);
+    output.set_glyphs(glyphs);
 
+    let detect_cell_aspect = args.cell_aspect.is_none();
     let mut game = game::Game::new(args, input, output, terminal_size);
+    if detect_cell_aspect {
+        game.set_aspect_source(Box::new(signals::cell_aspect));
+    }
+    if let Some(spectators) = spectators {
+        game.set_spectators(spectators);
+    }
+    game.set_size_source(signals::watch_resize()?);
+    game.set_suspend(Box::new(signals::suspend));
+    if let Some(high_scores) = HighScores::load() {
+        game.set_high_scores(high_scores);
+    }
+    if let Some(settings) = settings {
+        game.set_settings(settings);
+    }
 
-    game.run();
+    match panic::catch_unwind(AssertUnwindSafe(|| game.run())) {
+        Ok(result) => result?,
+        // Exit right away, since dropping the game would switch screens again and hide the
+        // message
+        Err(_) => exit_after_panic(&game.crash_report()),
+    }
+
+    Ok(())
+}
+
+// Run a subcommand that takes over the terminal, writing a crash report if it panics
+fn run_guarded(
+    command: &Command,
+    f: impl FnOnce() -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    crash::install()?;
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(_) => exit_after_panic(&format!("Command: {command:?}")),
+    }
+}
+
+// The panic hook already restored the terminal and printed the message, so all that is left is
+// the crash report
+fn exit_after_panic(details: &str) -> ! {
+    match crash::write_report(details) {
+        Ok(path) => eprintln!("A crash report was written to {}", path.display()),
+        Err(err) => eprintln!("Failed to write a crash report: {err}"),
+    }
+    process::exit(101);
+}
 
+fn run_command(command: Command, args: &parser::ArgsParser) -> Result<(), Box<dyn Error>> {
+    match command.clone() {
+        Command::Serve {
+            port,
+            host,
+            players,
+            width,
+            height,
+        } => {
+            let options = net_server::ServerOptions {
+                players: players as usize,
+                width,
+                height,
+                tick: Duration::from_millis(args.speed.value()),
+            };
+            net_server::Server::bind(&format!("{host}:{port}"), options)?.run()?;
+        }
+        Command::Join { addr } => {
+            let key_scheme = args.movement_key_scheme;
+            run_guarded(&command, || {
+                net_client::Client::connect(&addr, key_scheme)?.run()
+            })?;
+        }
+        Command::TelnetServe {
+            port,
+            host,
+            max_sessions,
+        } => {
+            let addr = format!("{host}:{port}");
+            telnet::TelnetServer::bind(&addr, args.clone(), max_sessions.into())?.run()?;
+        }
+        Command::Arena {
+            bots,
+            width,
+            height,
+            timeout,
+        } => {
+            let options = arena::ArenaOptions {
+                bots,
+                width,
+                height,
+                timeout: Duration::from_millis(timeout),
+                tick: Duration::from_millis(args.speed.value()),
+            };
+            run_guarded(&command, || Ok(arena::Arena::new(options)?.run()?))?;
+        }
+        Command::Watch { addr } => {
+            run_guarded(&command, || spectator::Watcher::connect(&addr)?.run())?;
+        }
+    }
     Ok(())
 }
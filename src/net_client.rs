@@ -0,0 +1,193 @@
+use std::error::Error;
+use std::io::{stdin, stdout, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::IntoAlternateScreen;
+use termion::terminal_size;
+
+use crate::error::GameError;
+use crate::game_input::{GameInput, KeyPress};
+use crate::game_output::GameOutput;
+use crate::net_protocol::{ClientMessage, ServerMessage};
+use crate::parser::MovementKeyScheme;
+use crate::remote_game::RemoteGame;
+
+#[derive(Debug, PartialEq)]
+enum ClientState {
+    Lobby,
+    InProgress,
+    GameOver,
+}
+
+pub struct Client {
+    stream: TcpStream,
+    messages: Receiver<ServerMessage>,
+    player: usize,
+    players: usize,
+    state: ClientState,
+    game: RemoteGame,
+    input: GameInput,
+    output: GameOutput,
+}
+
+impl Client {
+    pub fn connect(
+        addr: &str,
+        movement_key_scheme: MovementKeyScheme,
+    ) -> Result<Self, Box<dyn Error>> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut line = String::new();
+        BufRead::read_line(&mut reader, &mut line)?;
+        let (player, players, width, height) = match ServerMessage::decode(line.trim_end()) {
+            Some(ServerMessage::Welcome {
+                player,
+                players,
+                width,
+                height,
+            }) => (player, players, width, height),
+            Some(ServerMessage::Full) => {
+                return Err("the server is full or a round is running".into())
+            }
+            _ => return Err("unexpected reply from the server".into()),
+        };
+
+        if width == 0 || height == 0 {
+            return Err("the server sent an empty board".into());
+        }
+        // Centre the board, leaving room for the border
+        let Some(game) = RemoteGame::centred(width, height, terminal_size()?) else {
+            return Err(format!(
+                "terminal is too small for the server's {width}x{height} board, \
+                 it needs at least {}x{}",
+                u32::from(width) + 2,
+                u32::from(height) + 2
+            )
+            .into());
+        };
+
+        // Forward server messages from a background thread; the channel closes with the connection
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else { break };
+                if let Some(message) = ServerMessage::decode(&line) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        let input = stdin().keys();
+        let input = GameInput::new(input, &[movement_key_scheme]);
+        let output = stdout().into_raw_mode()?.into_alternate_screen()?;
+        let output = GameOutput::new(output);
+        Ok(Self {
+            stream,
+            messages,
+            player,
+            players,
+            state: ClientState::Lobby,
+            game,
+            input,
+            output,
+        })
+    }
+
+    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        let result = self.main_loop();
+        self.output.clear_screen()?;
+        self.output.show_cursor()?;
+        self.output.render()?;
+        result
+    }
+
+    fn main_loop(&mut self) -> Result<(), Box<dyn Error>> {
+        self.draw_lobby(0, 0)?;
+        loop {
+            loop {
+                match self.messages.try_recv() {
+                    Ok(message) => self.handle_message(message)?,
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        return Err("connection to the server was lost".into())
+                    }
+                }
+            }
+            for keypress in self.input.get_keypresses() {
+                match (keypress, &self.state) {
+                    (KeyPress::Quit, _) => {
+                        let _ = self.send(ClientMessage::Quit);
+                        return Ok(());
+                    }
+                    (KeyPress::Pause, ClientState::Lobby | ClientState::GameOver) => {
+                        self.send(ClientMessage::Ready)?;
+                        self.state = ClientState::Lobby;
+                    }
+                    (KeyPress::DirectionKey(_, direction), ClientState::InProgress) => {
+                        self.send(ClientMessage::Turn(direction))?;
+                    }
+                    _ => (),
+                }
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn handle_message(&mut self, message: ServerMessage) -> Result<(), GameError> {
+        match message {
+            ServerMessage::Lobby { connected, ready } if self.state == ClientState::Lobby => {
+                self.draw_lobby(connected, ready)?;
+            }
+            ServerMessage::Snapshot { food, snakes } => {
+                self.game.apply_snapshot(&food, &snakes);
+                self.state = ClientState::InProgress;
+                self.game.draw_board(&mut self.output)?;
+                self.output.render()?;
+            }
+            ServerMessage::Tick { food, snakes } if self.state == ClientState::InProgress => {
+                self.game.apply_tick(&food, &snakes, &mut self.output)?;
+                self.game.draw_snakes(&mut self.output)?;
+                self.output.draw_food(&self.game.food)?;
+                self.output.render()?;
+            }
+            ServerMessage::Over { winner } => {
+                self.state = ClientState::GameOver;
+                self.draw_results(winner)?;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, message: ClientMessage) -> std::io::Result<()> {
+        writeln!(self.stream, "{}", message.encode())
+    }
+
+    fn draw_lobby(&mut self, connected: usize, ready: usize) -> Result<(), GameError> {
+        self.output.clear_screen()?;
+        self.output
+            .draw_lobby_message(self.player, self.players, connected, ready)?;
+        self.output.render()
+    }
+
+    fn draw_results(&mut self, winner: Option<usize>) -> Result<(), GameError> {
+        let title = match winner {
+            Some(player) if player == self.player => String::from("you|win!"),
+            Some(player) => format!("player {}|wins!", player + 1),
+            None if self.players == 1 => String::from("game|over!"),
+            None => String::from("it's a|draw!"),
+        };
+        let results = self.game.results();
+        self.output.clear_screen()?;
+        self.output.draw_winner_message(&title, &results)?;
+        self.output.render()
+    }
+}
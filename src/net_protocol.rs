@@ -0,0 +1,417 @@
+use std::collections::VecDeque;
+
+use crate::game_instance::{Direction, GameGrid, GameInstance, GridCell, Snake};
+
+// Every message is a single line of space separated fields. Lists of snakes are separated by ';'.
+//
+// Client to server:
+//   READY | DIR <up|down|left|right> | QUIT
+// Server to client:
+//   WELCOME <player> <players> <width> <height>
+//   SPECTATE <players> <width> <height>
+//   FULL
+//   LOBBY <connected> <ready>
+//   SNAPSHOT <food x> <food y>;<alive> <direction> <food eaten> <x>,<y> ...;...
+//   TICK <food x> <food y>;M <x> <y> <grew>|D|-;...
+//   LEFT <player>
+//   OVER <winner|->
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClientMessage {
+    Ready,
+    Turn(Direction),
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnakeState {
+    pub alive: bool,
+    pub direction: Direction,
+    pub food_eaten: usize,
+    pub body: Vec<GridCell>,
+}
+
+// What happened to a snake during one tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnakeDelta {
+    Moved { head: GridCell, grew: bool },
+    Died,
+    // Snake was already dead before the tick
+    Unchanged,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    Welcome {
+        player: usize,
+        players: usize,
+        width: u16,
+        height: u16,
+    },
+    // Sent to spectators before every round
+    Spectate {
+        players: usize,
+        width: u16,
+        height: u16,
+    },
+    Full,
+    Lobby {
+        connected: usize,
+        ready: usize,
+    },
+    // Full game state, sent when a round starts
+    Snapshot {
+        food: GridCell,
+        snakes: Vec<SnakeState>,
+    },
+    Tick {
+        food: GridCell,
+        snakes: Vec<SnakeDelta>,
+    },
+    Left {
+        player: usize,
+    },
+    Over {
+        winner: Option<usize>,
+    },
+}
+
+fn encode_direction(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
+
+fn decode_direction(field: &str) -> Option<Direction> {
+    match field {
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+// Cells are sent relative to the top-left cell of the grid
+fn relative(cell: &GridCell, grid: &GameGrid) -> GridCell {
+    GridCell {
+        x: cell.x - grid.x_min,
+        y: cell.y - grid.y_min,
+    }
+}
+
+fn decode_cell(x: Option<&str>, y: Option<&str>) -> Option<GridCell> {
+    Some(GridCell {
+        x: x?.parse().ok()?,
+        y: y?.parse().ok()?,
+    })
+}
+
+impl ClientMessage {
+    pub fn encode(&self) -> String {
+        match self {
+            Self::Ready => String::from("READY"),
+            Self::Turn(direction) => format!("DIR {}", encode_direction(*direction)),
+            Self::Quit => String::from("QUIT"),
+        }
+    }
+
+    pub fn decode(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let message = match fields.next()? {
+            "READY" => Self::Ready,
+            "DIR" => Self::Turn(decode_direction(fields.next()?)?),
+            "QUIT" => Self::Quit,
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+impl SnakeState {
+    pub fn from_snake(snake: &Snake, grid: &GameGrid) -> Self {
+        Self {
+            alive: snake.alive,
+            direction: snake.direction,
+            food_eaten: snake.food_eaten,
+            body: snake.body.iter().map(|cell| relative(cell, grid)).collect(),
+        }
+    }
+
+    // Rebuild the snake, mapping every cell onto a local grid
+    pub fn to_snake(&self, local_cell: impl Fn(&GridCell) -> GridCell) -> Snake {
+        Snake {
+            body: self.body.iter().map(local_cell).collect::<VecDeque<_>>(),
+            old_tail: None,
+            direction: self.direction,
+            alive: self.alive,
+            food_eaten: self.food_eaten,
+            death: None,
+        }
+    }
+}
+
+impl ServerMessage {
+    pub fn spectate(instance: &GameInstance) -> Self {
+        Self::Spectate {
+            players: instance.snakes.len(),
+            width: instance.grid.width(),
+            height: instance.grid.height(),
+        }
+    }
+
+    pub fn snapshot(instance: &GameInstance) -> Self {
+        let grid = &instance.grid;
+        Self::Snapshot {
+            food: relative(&instance.food, grid),
+            snakes: instance
+                .snakes
+                .iter()
+                .map(|snake| SnakeState::from_snake(snake, grid))
+                .collect(),
+        }
+    }
+
+    // Describe the last tick, given which snakes were alive before it
+    pub fn tick(instance: &GameInstance, alive_before: &[bool]) -> Self {
+        let snakes = instance
+            .snakes
+            .iter()
+            .zip(alive_before)
+            .map(|(snake, was_alive)| match (was_alive, snake.alive) {
+                (true, true) => SnakeDelta::Moved {
+                    head: relative(snake.get_head(), &instance.grid),
+                    grew: snake.old_tail.is_none(),
+                },
+                (true, false) => SnakeDelta::Died,
+                (false, _) => SnakeDelta::Unchanged,
+            })
+            .collect();
+        Self::Tick {
+            food: relative(&instance.food, &instance.grid),
+            snakes,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        match self {
+            Self::Welcome {
+                player,
+                players,
+                width,
+                height,
+            } => format!("WELCOME {player} {players} {width} {height}"),
+            Self::Spectate {
+                players,
+                width,
+                height,
+            } => format!("SPECTATE {players} {width} {height}"),
+            Self::Full => String::from("FULL"),
+            Self::Lobby { connected, ready } => format!("LOBBY {connected} {ready}"),
+            Self::Snapshot { food, snakes } => {
+                let mut line = format!("SNAPSHOT {} {}", food.x, food.y);
+                for snake in snakes {
+                    line += &format!(
+                        ";{} {} {}",
+                        snake.alive as u8,
+                        encode_direction(snake.direction),
+                        snake.food_eaten
+                    );
+                    for cell in &snake.body {
+                        line += &format!(" {},{}", cell.x, cell.y);
+                    }
+                }
+                line
+            }
+            Self::Tick { food, snakes } => {
+                let mut line = format!("TICK {} {}", food.x, food.y);
+                for snake in snakes {
+                    line += &match snake {
+                        SnakeDelta::Moved { head, grew } => {
+                            format!(";M {} {} {}", head.x, head.y, *grew as u8)
+                        }
+                        SnakeDelta::Died => String::from(";D"),
+                        SnakeDelta::Unchanged => String::from(";-"),
+                    };
+                }
+                line
+            }
+            Self::Left { player } => format!("LEFT {player}"),
+            Self::Over { winner } => match winner {
+                Some(player) => format!("OVER {player}"),
+                None => String::from("OVER -"),
+            },
+        }
+    }
+
+    pub fn decode(line: &str) -> Option<Self> {
+        let mut parts = line.split(';');
+        let mut fields = parts.next()?.split_whitespace();
+        let message = match fields.next()? {
+            "WELCOME" => Self::Welcome {
+                player: fields.next()?.parse().ok()?,
+                players: fields.next()?.parse().ok()?,
+                width: fields.next()?.parse().ok()?,
+                height: fields.next()?.parse().ok()?,
+            },
+            "SPECTATE" => Self::Spectate {
+                players: fields.next()?.parse().ok()?,
+                width: fields.next()?.parse().ok()?,
+                height: fields.next()?.parse().ok()?,
+            },
+            "FULL" => Self::Full,
+            "LOBBY" => Self::Lobby {
+                connected: fields.next()?.parse().ok()?,
+                ready: fields.next()?.parse().ok()?,
+            },
+            "SNAPSHOT" => {
+                let food = decode_cell(fields.next(), fields.next())?;
+                let snakes = parts
+                    .map(|part| {
+                        let mut fields = part.split_whitespace();
+                        let alive = fields.next()? == "1";
+                        let direction = decode_direction(fields.next()?)?;
+                        let food_eaten = fields.next()?.parse().ok()?;
+                        let body = fields
+                            .map(|cell| {
+                                let mut xy = cell.split(',');
+                                decode_cell(xy.next(), xy.next())
+                            })
+                            .collect::<Option<Vec<_>>>()?;
+                        Some(SnakeState {
+                            alive,
+                            direction,
+                            food_eaten,
+                            body,
+                        })
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                Self::Snapshot { food, snakes }
+            }
+            "TICK" => {
+                let food = decode_cell(fields.next(), fields.next())?;
+                let snakes = parts
+                    .map(|part| {
+                        let mut fields = part.split_whitespace();
+                        match fields.next()? {
+                            "M" => Some(SnakeDelta::Moved {
+                                head: decode_cell(fields.next(), fields.next())?,
+                                grew: fields.next()? == "1",
+                            }),
+                            "D" => Some(SnakeDelta::Died),
+                            "-" => Some(SnakeDelta::Unchanged),
+                            _ => None,
+                        }
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                Self::Tick { food, snakes }
+            }
+            "LEFT" => Self::Left {
+                player: fields.next()?.parse().ok()?,
+            },
+            "OVER" => Self::Over {
+                winner: fields.next()?.parse().ok(),
+            },
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(x: u16, y: u16) -> GridCell {
+        GridCell { x, y }
+    }
+
+    #[test]
+    fn client_messages_round_trip() {
+        let mut messages = vec![ClientMessage::Ready, ClientMessage::Quit];
+        messages.extend(Direction::ALL.map(ClientMessage::Turn));
+        for message in messages {
+            assert_eq!(ClientMessage::decode(&message.encode()), Some(message));
+        }
+        assert_eq!(ClientMessage::decode("DIR sideways"), None);
+        assert_eq!(ClientMessage::decode(""), None);
+    }
+
+    #[test]
+    fn server_messages_round_trip() {
+        let messages = [
+            ServerMessage::Welcome {
+                player: 1,
+                players: 3,
+                width: 40,
+                height: 20,
+            },
+            ServerMessage::Spectate {
+                players: 2,
+                width: 11,
+                height: 11,
+            },
+            ServerMessage::Full,
+            ServerMessage::Lobby {
+                connected: 2,
+                ready: 1,
+            },
+            ServerMessage::Snapshot {
+                food: cell(3, 4),
+                snakes: vec![
+                    SnakeState {
+                        alive: true,
+                        direction: Direction::Left,
+                        food_eaten: 2,
+                        body: vec![cell(5, 1), cell(6, 1), cell(7, 1)],
+                    },
+                    SnakeState {
+                        alive: false,
+                        direction: Direction::Up,
+                        food_eaten: 0,
+                        body: vec![cell(0, 9), cell(0, 10)],
+                    },
+                ],
+            },
+            ServerMessage::Tick {
+                food: cell(0, 0),
+                snakes: vec![
+                    SnakeDelta::Moved {
+                        head: cell(4, 1),
+                        grew: true,
+                    },
+                    SnakeDelta::Moved {
+                        head: cell(9, 2),
+                        grew: false,
+                    },
+                    SnakeDelta::Died,
+                    SnakeDelta::Unchanged,
+                ],
+            },
+            ServerMessage::Left { player: 2 },
+            ServerMessage::Over { winner: Some(1) },
+            ServerMessage::Over { winner: None },
+        ];
+        for message in messages {
+            assert_eq!(ServerMessage::decode(&message.encode()), Some(message));
+        }
+    }
+
+    #[test]
+    fn draws_are_sent_as_a_dash() {
+        let draw = ServerMessage::Over { winner: None };
+        assert_eq!(draw.encode(), "OVER -");
+        assert_eq!(ServerMessage::decode("OVER -"), Some(draw));
+    }
+
+    #[test]
+    fn malformed_server_messages_are_rejected() {
+        assert_eq!(ServerMessage::decode("WELCOME 0 2 40"), None);
+        assert_eq!(ServerMessage::decode("TICK 1 1;X"), None);
+        assert_eq!(ServerMessage::decode("SNAPSHOT 1 1;1 up 0 3,x"), None);
+        assert_eq!(ServerMessage::decode("HELLO"), None);
+    }
+}
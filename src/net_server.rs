@@ -0,0 +1,488 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::thread;
+use std::time::Duration;
+
+use crate::game_instance::{DeathCause, Direction, GameGrid, GameInstance};
+use crate::net_protocol::{ClientMessage, ServerMessage};
+
+// How often the lobby checks for new connections and messages
+const LOBBY_POLL: Duration = Duration::from_millis(20);
+// Clients that stop reading for this long are disconnected
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+// Messages queued for a client that has not been sent yet. A client this far behind is
+// disconnected, so a slow connection never holds up the game.
+const OUTBOX_SIZE: usize = 64;
+
+enum Event {
+    Message(u64, ClientMessage),
+    Disconnected(u64),
+}
+
+struct Client {
+    id: u64,
+    stream: TcpStream,
+    // Lines for the writer thread of the client
+    outbox: SyncSender<String>,
+    ready: bool,
+}
+
+pub struct ServerOptions {
+    pub players: usize,
+    pub width: u16,
+    pub height: u16,
+    pub tick: Duration,
+}
+
+pub struct Server {
+    options: ServerOptions,
+    listener: TcpListener,
+    // One slot per player, `None` while nobody is connected to it
+    clients: Vec<Option<Client>>,
+    events: Receiver<Event>,
+    event_sender: Sender<Event>,
+    next_id: u64,
+}
+
+impl Server {
+    pub fn bind(addr: &str, options: ServerOptions) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let (event_sender, events) = mpsc::channel();
+        Ok(Self {
+            clients: (0..options.players).map(|_| None).collect(),
+            options,
+            listener,
+            events,
+            event_sender,
+            next_id: 0,
+        })
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        println!(
+            "Waiting for {} players on {}",
+            self.options.players,
+            self.listener.local_addr()?
+        );
+        loop {
+            self.lobby()?;
+            self.play_round()?;
+        }
+    }
+
+    // Wait until every player slot is taken and every player is ready
+    fn lobby(&mut self) -> io::Result<()> {
+        self.broadcast_lobby();
+        loop {
+            let mut changed = self.accept_clients(true)?;
+            while let Ok(event) = self.events.try_recv() {
+                changed = true;
+                match event {
+                    Event::Message(id, ClientMessage::Ready) => {
+                        if let Some(client) = self.client_mut(id) {
+                            client.ready = true;
+                        }
+                    }
+                    Event::Message(id, ClientMessage::Quit) | Event::Disconnected(id) => {
+                        self.disconnect(id);
+                    }
+                    Event::Message(_, ClientMessage::Turn(_)) => (),
+                }
+            }
+            if changed {
+                self.broadcast_lobby();
+            }
+            if self
+                .clients
+                .iter()
+                .all(|slot| slot.as_ref().is_some_and(|c| c.ready))
+            {
+                return Ok(());
+            }
+            thread::sleep(LOBBY_POLL);
+        }
+    }
+
+    fn play_round(&mut self) -> io::Result<()> {
+        let grid = GameGrid::with_dimensions(self.options.width, self.options.height);
+        let mut instance = GameInstance::with_players(grid, self.options.players);
+        self.broadcast(&ServerMessage::snapshot(&instance));
+
+        loop {
+            thread::sleep(self.options.tick);
+            // Nobody can join a running round
+            self.accept_clients(false)?;
+
+            let alive_before: Vec<bool> = instance.snakes.iter().map(|s| s.alive).collect();
+            // Keep only the latest direction of every player
+            let mut turns: Vec<Option<Direction>> = vec![None; self.options.players];
+            while let Ok(event) = self.events.try_recv() {
+                match event {
+                    Event::Message(id, ClientMessage::Turn(direction)) => {
+                        if let Some(player) = self.player_of(id) {
+                            turns[player] = Some(direction);
+                        }
+                    }
+                    Event::Message(id, ClientMessage::Quit) | Event::Disconnected(id) => {
+                        self.disconnect(id);
+                    }
+                    Event::Message(_, ClientMessage::Ready) => (),
+                }
+            }
+            // The snake of a player who left dies on this tick
+            for player in 0..self.options.players {
+                if self.clients[player].is_none() && instance.snakes[player].alive {
+                    instance.kill(player, DeathCause::Timeout);
+                    self.broadcast(&ServerMessage::Left { player });
+                }
+            }
+            for (player, direction) in turns.into_iter().enumerate() {
+                if let Some(direction) = direction {
+                    instance.turn(player, direction);
+                }
+            }
+
+            let tick = instance.game_cycle();
+            self.broadcast(&ServerMessage::tick(&instance, &alive_before));
+
+            // The last snake standing wins; a lone player plays until the snake dies or fills
+            // the board
+            let alive = instance.alive_count();
+            let last_standing = self.options.players > 1 && alive == 1;
+            if tick.board_full || alive == 0 || last_standing {
+                let winner = match alive {
+                    1 if last_standing || tick.board_full => {
+                        instance.snakes.iter().position(|snake| snake.alive)
+                    }
+                    _ => None,
+                };
+                self.broadcast(&ServerMessage::Over { winner });
+                for client in self.clients.iter_mut().flatten() {
+                    client.ready = false;
+                }
+                return Ok(());
+            }
+        }
+    }
+
+    // Accept pending connections, placing them in free slots when `open`. Returns whether any
+    // player joined.
+    fn accept_clients(&mut self, open: bool) -> io::Result<bool> {
+        let mut joined = false;
+        loop {
+            let mut stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(joined),
+                Err(err) => return Err(err),
+            };
+            // A connection that cannot be set up is dropped, like one that breaks
+            let configured = stream
+                .set_nonblocking(false)
+                .and_then(|_| stream.set_nodelay(true))
+                .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)));
+            if configured.is_err() {
+                continue;
+            }
+            let free_slot = self.clients.iter().position(Option::is_none);
+            let Some(player) = free_slot.filter(|_| open) else {
+                let _ = writeln!(stream, "{}", ServerMessage::Full.encode());
+                continue;
+            };
+
+            let (Ok(reader), Ok(writer)) = (stream.try_clone(), stream.try_clone()) else {
+                continue;
+            };
+            let id = self.next_id;
+            self.next_id += 1;
+            let welcome = ServerMessage::Welcome {
+                player,
+                players: self.options.players,
+                width: self.options.width,
+                height: self.options.height,
+            };
+            let outbox = Self::spawn_writer(writer);
+            if outbox.try_send(welcome.encode()).is_err() {
+                continue;
+            }
+            Self::spawn_reader(id, reader, self.event_sender.clone());
+            self.clients[player] = Some(Client {
+                id,
+                stream,
+                outbox,
+                ready: false,
+            });
+            joined = true;
+        }
+    }
+
+    // Forward every line received from a client to the server loop
+    fn spawn_reader(id: u64, stream: TcpStream, events: Sender<Event>) {
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else { break };
+                if let Some(message) = ClientMessage::decode(&line) {
+                    if events.send(Event::Message(id, message)).is_err() {
+                        return;
+                    }
+                }
+            }
+            let _ = events.send(Event::Disconnected(id));
+        });
+    }
+
+    // Send the lines queued for a client until its connection breaks or times out, which also
+    // ends its reader
+    fn spawn_writer(mut stream: TcpStream) -> SyncSender<String> {
+        let (outbox, lines) = mpsc::sync_channel::<String>(OUTBOX_SIZE);
+        thread::spawn(move || {
+            for line in lines {
+                if writeln!(stream, "{line}").is_err() {
+                    let _ = stream.shutdown(std::net::Shutdown::Both);
+                    return;
+                }
+            }
+        });
+        outbox
+    }
+
+    fn player_of(&self, id: u64) -> Option<usize> {
+        self.clients
+            .iter()
+            .position(|slot| slot.as_ref().is_some_and(|client| client.id == id))
+    }
+
+    fn client_mut(&mut self, id: u64) -> Option<&mut Client> {
+        self.clients
+            .iter_mut()
+            .flatten()
+            .find(|client| client.id == id)
+    }
+
+    fn disconnect(&mut self, id: u64) {
+        if let Some(player) = self.player_of(id) {
+            if let Some(client) = self.clients[player].take() {
+                let _ = client.stream.shutdown(std::net::Shutdown::Both);
+            }
+        }
+    }
+
+    fn broadcast_lobby(&mut self) {
+        let connected = self.clients.iter().flatten().count();
+        let ready = self.clients.iter().flatten().filter(|c| c.ready).count();
+        self.broadcast(&ServerMessage::Lobby { connected, ready });
+    }
+
+    // Queue a message for every player, dropping the ones whose connection broke or who fell
+    // too far behind
+    fn broadcast(&mut self, message: &ServerMessage) {
+        let line = message.encode();
+        for slot in self.clients.iter_mut() {
+            if let Some(client) = slot {
+                if client.outbox.try_send(line.clone()).is_err() {
+                    let _ = client.stream.shutdown(std::net::Shutdown::Both);
+                    *slot = None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net_protocol::SnakeDelta;
+
+    // Client side of a connection to the test server
+    struct TestClient {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl TestClient {
+        fn connect(addr: std::net::SocketAddr) -> Self {
+            let stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            Self {
+                reader: BufReader::new(stream.try_clone().unwrap()),
+                writer: stream,
+            }
+        }
+
+        fn send(&mut self, message: ClientMessage) {
+            writeln!(self.writer, "{}", message.encode()).unwrap();
+        }
+
+        fn receive(&mut self) -> ServerMessage {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            ServerMessage::decode(line.trim_end()).unwrap()
+        }
+
+        // Skip lobby updates until the next other message
+        fn receive_after_lobby(&mut self) -> ServerMessage {
+            loop {
+                match self.receive() {
+                    ServerMessage::Lobby { .. } => (),
+                    message => return message,
+                }
+            }
+        }
+    }
+
+    fn test_server() -> Server {
+        let options = ServerOptions {
+            players: 2,
+            width: 12,
+            height: 10,
+            tick: Duration::from_millis(10),
+        };
+        Server::bind("127.0.0.1:0", options).unwrap()
+    }
+
+    // Run a two-player server and return its address
+    fn start_server() -> std::net::SocketAddr {
+        let mut server = test_server();
+        let addr = server.listener.local_addr().unwrap();
+        thread::spawn(move || server.run());
+        addr
+    }
+
+    // Connect both players and get them into a round
+    fn start_round() -> [TestClient; 2] {
+        let addr = start_server();
+        let mut clients = [TestClient::connect(addr), TestClient::connect(addr)];
+        for client in &mut clients {
+            client.send(ClientMessage::Ready);
+        }
+        for client in &mut clients {
+            assert!(matches!(
+                client.receive_after_lobby(),
+                ServerMessage::Welcome { .. }
+            ));
+            assert!(matches!(
+                client.receive_after_lobby(),
+                ServerMessage::Snapshot { .. }
+            ));
+        }
+        clients
+    }
+
+    #[test]
+    fn two_players_get_through_the_lobby_into_a_round() {
+        let addr = start_server();
+        let mut clients = [TestClient::connect(addr), TestClient::connect(addr)];
+        for (player, client) in clients.iter_mut().enumerate() {
+            let welcome = ServerMessage::Welcome {
+                player,
+                players: 2,
+                width: 12,
+                height: 10,
+            };
+            assert_eq!(client.receive(), welcome);
+            assert!(matches!(client.receive(), ServerMessage::Lobby { .. }));
+        }
+        for client in &mut clients {
+            client.send(ClientMessage::Ready);
+        }
+        for client in &mut clients {
+            let ServerMessage::Snapshot { snakes, .. } = client.receive_after_lobby() else {
+                panic!("expected a snapshot");
+            };
+            assert_eq!(snakes.len(), 2);
+            assert!(snakes.iter().all(|snake| snake.alive));
+            let ServerMessage::Tick { snakes, .. } = client.receive() else {
+                panic!("expected a tick");
+            };
+            assert_eq!(snakes.len(), 2);
+            assert!(snakes
+                .iter()
+                .all(|snake| matches!(snake, SnakeDelta::Moved { .. })));
+        }
+    }
+
+    #[test]
+    fn a_player_leaving_mid_round_loses_it() {
+        let [mut stays, leaves] = start_round();
+        drop(leaves);
+        loop {
+            match stays.receive() {
+                ServerMessage::Left { player } => {
+                    assert_eq!(player, 1);
+                    break;
+                }
+                ServerMessage::Tick { .. } => (),
+                message => panic!("unexpected {message:?}"),
+            }
+        }
+        let ServerMessage::Tick { snakes, .. } = stays.receive() else {
+            panic!("expected a tick");
+        };
+        assert!(matches!(snakes[0], SnakeDelta::Moved { .. }));
+        assert_eq!(snakes[1], SnakeDelta::Died);
+        assert_eq!(stays.receive(), ServerMessage::Over { winner: Some(0) });
+    }
+
+    #[test]
+    fn a_player_leaving_the_lobby_frees_the_slot() {
+        let addr = start_server();
+        let mut stays = TestClient::connect(addr);
+        let leaves = TestClient::connect(addr);
+        assert!(matches!(
+            stays.receive(),
+            ServerMessage::Welcome { player: 0, .. }
+        ));
+        // Wait for the second player to be counted before it leaves again
+        while stays.receive()
+            != (ServerMessage::Lobby {
+                connected: 2,
+                ready: 0,
+            })
+        {}
+        drop(leaves);
+        assert_eq!(
+            stays.receive(),
+            ServerMessage::Lobby {
+                connected: 1,
+                ready: 0
+            }
+        );
+
+        let mut joins = TestClient::connect(addr);
+        assert!(matches!(
+            joins.receive(),
+            ServerMessage::Welcome { player: 1, .. }
+        ));
+        assert_eq!(
+            stays.receive(),
+            ServerMessage::Lobby {
+                connected: 2,
+                ready: 0
+            }
+        );
+    }
+
+    #[test]
+    fn a_client_that_falls_behind_is_dropped() {
+        let mut server = test_server();
+        let addr = server.listener.local_addr().unwrap();
+        let _connection = TcpStream::connect(addr).unwrap();
+        let (stream, _) = server.listener.accept().unwrap();
+        // Nothing takes messages out of this outbox, like a writer stuck on a stalled connection
+        let (outbox, _lines) = mpsc::sync_channel(1);
+        server.clients[0] = Some(Client {
+            id: 0,
+            stream,
+            outbox,
+            ready: true,
+        });
+
+        server.broadcast(&ServerMessage::Full);
+        assert!(server.clients[0].is_some());
+        server.broadcast(&ServerMessage::Full);
+        assert!(server.clients[0].is_none());
+    }
+}
@@ -1,20 +1,158 @@
-use clap::{Parser, ValueEnum};
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Parser, Debug)]
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::glyphs::GlyphSet;
+use crate::theme::ColorDepth;
+
+#[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 pub struct ArgsParser {
+    /// Share of the terminal the board takes up
     #[arg(short, long, value_enum, default_value_t = GridSize::Small)]
     pub grid_size: GridSize,
-    #[arg(short, long, value_enum, default_value_t = Speed::High)]
+    /// slow, moderate, high or the milliseconds per tick
+    #[arg(short, long, default_value_t = Speed::High)]
     pub speed: Speed,
+    /// Height of a terminal cell divided by its width, which slows down vertical moves. Detected
+    /// when the terminal reports its size in pixels.
+    #[arg(long, value_parser = parse_cell_aspect)]
+    pub cell_aspect: Option<f64>,
+    /// How grid cells are drawn on the terminal
+    #[arg(long, value_enum, default_value_t = RenderMode::Normal)]
+    pub render_mode: RenderMode,
+    /// How the snakes' segments look
+    #[arg(long, value_enum, default_value_t = SnakeSkin::Classic)]
+    pub skin: SnakeSkin,
+    /// classic, dark, solarized, high-contrast, colorblind-safe, or a theme file
+    #[arg(long, default_value = "classic")]
+    pub theme: String,
+    /// Colours to use
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
+    pub color: ColorMode,
+    /// Characters the board, snakes and banners are drawn with
+    #[arg(long, value_enum, default_value_t = GlyphMode::Auto)]
+    pub glyphs: GlyphMode,
+    /// Keys that steer the snake
     #[arg(short, long, value_enum, default_value_t = MovementKeyScheme::Arrows)]
     pub movement_key_scheme: MovementKeyScheme,
+    /// Who the snakes on the board belong to
+    #[arg(long, value_enum, default_value_t = GameMode::SinglePlayer)]
+    pub mode: GameMode,
+    /// Number of computer-controlled snakes in versus mode
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u8).range(1..=7))]
+    pub opponents: u8,
+    /// How well the computer-controlled snakes play in versus mode
+    #[arg(long, value_enum, default_value_t = Difficulty::Normal)]
+    pub difficulty: Difficulty,
+    /// How a versus round is won
+    #[arg(long, value_enum, default_value_t = WinCondition::LastStanding)]
+    pub win_condition: WinCondition,
+    /// Round length in seconds for the most-food win condition
+    #[arg(long, default_value_t = 120)]
+    pub time_limit: u64,
+    /// Stream the game to watchers on `host:port` or `unix:<path>`
+    #[arg(long)]
+    pub spectate: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    #[command(about = "Host a networked game that other players can join")]
+    Serve {
+        #[arg(short, long)]
+        port: u16,
+        /// Address to listen on; 0.0.0.0 lets other machines join
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(1..=8))]
+        players: u8,
+        #[arg(long, default_value_t = 40, value_parser = clap::value_parser!(u16).range(10..=500))]
+        width: u16,
+        #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u16).range(10..=200))]
+        height: u16,
+    },
+    #[command(about = "Join a networked game hosted with 'serve'")]
+    Join {
+        /// Address of the server, e.g. 127.0.0.1:4000
+        addr: String,
+    },
+    #[command(about = "Let people play over telnet, each connection getting its own game")]
+    TelnetServe {
+        #[arg(short, long, default_value_t = 2323)]
+        port: u16,
+        /// Address to listen on; 0.0.0.0 lets other machines connect
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Games running at once; further connections are turned away
+        #[arg(long, default_value_t = 32, value_parser = clap::value_parser!(u16).range(1..))]
+        max_sessions: u16,
+    },
+    #[command(about = "Play Battlesnake bots against each other on this terminal")]
+    Arena {
+        /// Base URLs of the bots, e.g. http://127.0.0.1:8000, one snake per bot
+        #[arg(required = true, num_args = 1..=8)]
+        bots: Vec<String>,
+        #[arg(long, default_value_t = 11, value_parser = clap::value_parser!(u16).range(7..=500))]
+        width: u16,
+        #[arg(long, default_value_t = 11, value_parser = clap::value_parser!(u16).range(7..=200))]
+        height: u16,
+        /// Milliseconds a bot has to answer a request
+        #[arg(long, default_value_t = 500)]
+        timeout: u64,
+    },
+    #[command(about = "Watch a game started with '--spectate'")]
+    Watch {
+        /// Address given to '--spectate', e.g. 127.0.0.1:5000 or unix:/tmp/rs_snake.sock
+        addr: String,
+    },
+}
+
+impl ArgsParser {
+    // Movement keys of every player, indexed by player number
+    pub fn key_schemes(&self) -> Vec<MovementKeyScheme> {
+        match self.mode {
+            GameMode::SinglePlayer | GameMode::Versus => vec![self.movement_key_scheme],
+            // Player 1 uses WASD and player 2 the arrow keys, on the same keyboard
+            GameMode::TwoPlayer => vec![MovementKeyScheme::Wsad, MovementKeyScheme::Arrows],
+        }
+    }
+}
+
+fn parse_cell_aspect(aspect: &str) -> Result<f64, String> {
+    match aspect.parse() {
+        Ok(aspect @ 0.25..=4.0) => Ok(aspect),
+        _ => Err(String::from("expected a ratio from 0.25 to 4")),
+    }
+}
+
+// Name of an option value as given on the command line
+pub fn value_name<T: ValueEnum>(value: &T) -> String {
+    value
+        .to_possible_value()
+        .map_or_else(String::new, |value| value.get_name().to_string())
+}
+
+// The option value `step` places after `value`, wrapping around, for stepping through menus
+pub fn cycle_value<T: ValueEnum + Clone>(value: &T, step: isize) -> T {
+    let values = T::value_variants();
+    let index = values
+        .iter()
+        .position(|other| value_name(other) == value_name(value))
+        .unwrap_or(0);
+    values[(index as isize + step).rem_euclid(values.len() as isize) as usize].clone()
 }
 
 #[derive(ValueEnum, Clone, Copy, Debug)]
 pub enum GridSize {
+    /// 70% of the terminal
     Small,
+    /// 85% of the terminal
     Medium,
+    /// The whole terminal
     Large,
 }
 
@@ -28,25 +166,182 @@ impl GridSize {
     }
 }
 
-#[derive(ValueEnum, Clone, Copy, Debug)]
+// How grid cells are drawn on the terminal
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum RenderMode {
+    /// One character per cell
+    Normal,
+    /// Two characters per cell, so that cells are about as wide as they are high
+    Square,
+    /// Two cells above each other per character, drawn with half blocks
+    HalfBlock,
+    /// Two by four cells per character, drawn as braille dots
+    Braille,
+}
+
+// How the snakes' segments look
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SnakeSkin {
+    /// The letters of "Snake" on a flat colour
+    Classic,
+    /// Fading from head to tail
+    Gradient,
+    /// Bands of two segments in alternating shades
+    Stripes,
+    /// A body of box-drawing lines that shows its corners
+    Pipes,
+    /// Thinning out towards the tail
+    Tapered,
+}
+
+// Colours to use, detected from NO_COLOR, COLORTERM and TERM unless given
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ColorMode {
+    /// Detected from NO_COLOR, COLORTERM and TERM
+    Auto,
+    /// 24-bit colours
+    Truecolor,
+    /// The 256-colour palette
+    #[value(name = "256")]
+    Ansi256,
+    /// The 16 basic colours
+    #[value(name = "16")]
+    Ansi16,
+    /// No colours, with glyphs marking the cells instead
+    None,
+}
+
+impl ColorMode {
+    // The colour depth to use, if not left to detection
+    pub fn depth(&self) -> Option<ColorDepth> {
+        match self {
+            ColorMode::Auto => None,
+            ColorMode::Truecolor => Some(ColorDepth::TrueColor),
+            ColorMode::Ansi256 => Some(ColorDepth::Ansi256),
+            ColorMode::Ansi16 => Some(ColorDepth::Ansi16),
+            ColorMode::None => Some(ColorDepth::None),
+        }
+    }
+}
+
+// Characters to draw with, detected from TERM and the locale unless given
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum GlyphMode {
+    /// Detected from TERM and the locale
+    Auto,
+    /// Plain ASCII, for any terminal
+    Ascii,
+    /// Unicode symbols
+    Unicode,
+    /// Unicode with emoji for the food and crashes in square render mode
+    Emoji,
+}
+
+impl GlyphMode {
+    // The glyph set to use, if not left to detection
+    pub fn glyphs(&self) -> Option<GlyphSet> {
+        match self {
+            GlyphMode::Auto => None,
+            GlyphMode::Ascii => Some(GlyphSet::Ascii),
+            GlyphMode::Unicode => Some(GlyphSet::Unicode),
+            GlyphMode::Emoji => Some(GlyphSet::Emoji),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Speed {
     Slow,
     Moderate,
     High,
+    // Milliseconds per tick
+    Custom(u64),
 }
 
 impl Speed {
+    const PRESETS: [Speed; 3] = [Speed::Slow, Speed::Moderate, Speed::High];
+
     pub fn value(&self) -> u64 {
         match self {
             Speed::Slow => 120,
             Speed::Moderate => 90,
             Speed::High => 60,
+            Speed::Custom(millis) => *millis,
+        }
+    }
+
+    // The preset `step` places after this speed, wrapping around. A custom speed steps from the
+    // preset closest to it.
+    pub fn cycle(&self, step: isize) -> Speed {
+        let presets = Self::PRESETS.len() as isize;
+        let index = (0..presets)
+            .min_by_key(|&index| Self::PRESETS[index as usize].value().abs_diff(self.value()))
+            .unwrap_or(0);
+        Self::PRESETS[(index + step).rem_euclid(presets) as usize]
+    }
+}
+
+impl FromStr for Speed {
+    type Err = String;
+
+    fn from_str(speed: &str) -> Result<Self, Self::Err> {
+        match speed.to_lowercase().as_str() {
+            "slow" => Ok(Speed::Slow),
+            "moderate" => Ok(Speed::Moderate),
+            "high" => Ok(Speed::High),
+            millis => match millis.parse() {
+                Ok(millis @ 1..=10_000) => Ok(Speed::Custom(millis)),
+                _ => Err(String::from(
+                    "expected slow, moderate, high or a tick length of 1 to 10000 ms",
+                )),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Speed::Slow => write!(f, "slow"),
+            Speed::Moderate => write!(f, "moderate"),
+            Speed::High => write!(f, "high"),
+            Speed::Custom(millis) => write!(f, "{millis}"),
         }
     }
 }
 
 #[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
 pub enum MovementKeyScheme {
+    /// W, A, S and D
     Wsad,
+    /// The arrow keys
     Arrows,
 }
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum GameMode {
+    /// One snake on its own
+    SinglePlayer,
+    /// Two snakes sharing the keyboard, WASD against the arrow keys
+    TwoPlayer,
+    /// Your snake against computer-controlled ones
+    Versus,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Difficulty {
+    /// Heads for the food and now and then makes a random move
+    Easy,
+    /// Heads for the food, avoiding the cells right in front of it
+    Normal,
+    /// Follows the shortest path to the food, avoiding dead ends
+    Hard,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum WinCondition {
+    /// The last snake alive wins
+    LastStanding,
+    /// The snake that ate the most food within --time-limit seconds wins
+    MostFood,
+}
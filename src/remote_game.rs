@@ -0,0 +1,126 @@
+use crate::error::GameError;
+use crate::game_instance::{GameGrid, GridCell, Snake};
+use crate::game_output::GameOutput;
+use crate::net_protocol::{SnakeDelta, SnakeState};
+
+// Local copy of a game running elsewhere, placed on this terminal. Cells received from the
+// remote side are relative to the top-left cell of its grid.
+pub struct RemoteGame {
+    pub grid: GameGrid,
+    pub snakes: Vec<Snake>,
+    pub food: GridCell,
+    // Size of the remote grid
+    width: u16,
+    height: u16,
+}
+
+impl RemoteGame {
+    // Centre a `width` x `height` board on the terminal, if it fits next to the border
+    pub fn centred(width: u16, height: u16, terminal_size: (u16, u16)) -> Option<Self> {
+        let (term_x, term_y) = terminal_size;
+        if term_x < width.saturating_add(2) || term_y < height.saturating_add(2) {
+            return None;
+        }
+        Self::scaled(width, height, terminal_size)
+    }
+
+    // Like `centred`, but shrink the board to fit small terminals, merging neighbouring cells.
+    // A board without cells, which only a broken or hostile remote side sends, gives `None`.
+    pub fn scaled(width: u16, height: u16, terminal_size: (u16, u16)) -> Option<Self> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let (term_x, term_y) = terminal_size;
+        let local_width = width.min(term_x.saturating_sub(2)).max(1);
+        let local_height = height.min(term_y.saturating_sub(2)).max(1);
+        let x_min = (term_x.saturating_sub(local_width)) / 2 + 1;
+        let y_min = (term_y.saturating_sub(local_height)) / 2 + 1;
+        Some(Self {
+            grid: GameGrid::with_origin(x_min, y_min, local_width, local_height),
+            snakes: Vec::new(),
+            food: GridCell { x: x_min, y: y_min },
+            width,
+            height,
+        })
+    }
+
+    // Cells outside the remote grid are clamped to its edge
+    pub fn local_cell(&self, cell: &GridCell) -> GridCell {
+        let scale = |value: u16, remote: u16, local: u16| {
+            (value as u32 * local as u32 / remote as u32).min(local as u32 - 1) as u16
+        };
+        GridCell {
+            x: self.grid.x_min + scale(cell.x, self.width, self.grid.width()),
+            y: self.grid.y_min + scale(cell.y, self.height, self.grid.height()),
+        }
+    }
+
+    pub fn apply_snapshot(&mut self, food: &GridCell, snakes: &[SnakeState]) {
+        self.snakes = snakes
+            .iter()
+            .map(|snake| snake.to_snake(|cell| self.local_cell(cell)))
+            .collect();
+        self.food = self.local_cell(food);
+    }
+
+    // Apply one tick, undrawing snakes that died during it
+    pub fn apply_tick(
+        &mut self,
+        food: &GridCell,
+        deltas: &[SnakeDelta],
+        output: &mut GameOutput,
+    ) -> Result<(), GameError> {
+        for (player, delta) in deltas.iter().enumerate().take(self.snakes.len()) {
+            match *delta {
+                SnakeDelta::Moved { head, grew } => {
+                    let head = self.local_cell(&head);
+                    let snake = &mut self.snakes[player];
+                    snake.body.push_front(head);
+                    snake.old_tail = if grew {
+                        snake.food_eaten += 1;
+                        None
+                    } else {
+                        snake.body.pop_back()
+                    };
+                }
+                SnakeDelta::Died => {
+                    let snake = &mut self.snakes[player];
+                    snake.alive = false;
+                    output.undraw_snake(snake)?;
+                }
+                SnakeDelta::Unchanged => (),
+            }
+        }
+        self.food = self.local_cell(food);
+        Ok(())
+    }
+
+    pub fn draw_board(&self, output: &mut GameOutput) -> Result<(), GameError> {
+        output.clear_screen()?;
+        let (x_min, y_min, x_max, y_max) = self.grid.get_corners();
+        output.draw_border(x_min, x_max, y_min, y_max)?;
+        self.draw_snakes(output)?;
+        output.draw_food(&self.food)
+    }
+
+    pub fn draw_snakes(&self, output: &mut GameOutput) -> Result<(), GameError> {
+        let living = self.snakes.iter().enumerate();
+        output.draw_snakes(living.filter(|(_, snake)| snake.alive))
+    }
+
+    // One result line per snake for the winner screen
+    pub fn results(&self) -> Vec<String> {
+        self.snakes
+            .iter()
+            .enumerate()
+            .map(|(player, snake)| {
+                format!(
+                    "player {}: length {}, food {}",
+                    player + 1,
+                    snake.body.len(),
+                    snake.food_eaten
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,87 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use serde_json::{json, Value};
+
+use crate::parser::{value_name, ArgsParser};
+
+// `$XDG_CONFIG_HOME/rs_snake` or `~/.config/rs_snake`, or `None` without a home directory
+pub fn config_dir() -> Option<PathBuf> {
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config.join("rs_snake"))
+}
+
+// Choices made in the main menu, remembered between runs as JSON in the config directory
+pub struct Settings {
+    path: PathBuf,
+}
+
+impl Settings {
+    pub fn load() -> Option<Self> {
+        Some(Self {
+            path: config_dir()?.join("settings.json"),
+        })
+    }
+
+    // Use the saved choices for every option that was not given on the command line. A
+    // missing or unreadable file leaves `args` as it is.
+    pub fn apply(&self, args: &mut ArgsParser, matches: &ArgMatches) {
+        let Some(saved) = fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+        else {
+            return;
+        };
+        let saved = |id: &str| {
+            let given = matches.value_source(id) == Some(ValueSource::CommandLine);
+            (!given).then(|| &saved[id])
+        };
+        restore(saved("grid_size"), &mut args.grid_size);
+        if let Some(speed) = saved("speed")
+            .and_then(Value::as_str)
+            .and_then(|speed| speed.parse().ok())
+        {
+            args.speed = speed;
+        }
+        restore(saved("movement_key_scheme"), &mut args.movement_key_scheme);
+        restore(saved("mode"), &mut args.mode);
+        restore(saved("difficulty"), &mut args.difficulty);
+        restore(saved("skin"), &mut args.skin);
+        if let Some(opponents) = saved("opponents").and_then(Value::as_u64) {
+            args.opponents = opponents.clamp(1, 7) as u8;
+        }
+    }
+
+    pub fn save(&self, args: &ArgsParser) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let settings = json!({
+            "grid_size": value_name(&args.grid_size),
+            "speed": args.speed.to_string(),
+            "movement_key_scheme": value_name(&args.movement_key_scheme),
+            "mode": value_name(&args.mode),
+            "difficulty": value_name(&args.difficulty),
+            "opponents": args.opponents,
+            "skin": value_name(&args.skin),
+        });
+        fs::write(&self.path, settings.to_string())
+    }
+}
+
+// Replace `value` with a saved option value, if it names one
+fn restore<T: ValueEnum>(saved: Option<&Value>, value: &mut T) {
+    if let Some(restored) = saved
+        .and_then(Value::as_str)
+        .and_then(|name| T::from_str(name, true).ok())
+    {
+        *value = restored;
+    }
+}
@@ -0,0 +1,62 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use signal_hook::consts::{SIGTSTP, SIGWINCH};
+use termion::cursor;
+use termion::screen::ToAlternateScreen;
+
+use crate::crash;
+
+// Reports the current terminal size, or `None` when it is unknown or did not change
+pub type SizeSource = Box<dyn FnMut() -> Option<(u16, u16)> + Send>;
+
+// Measures the height of the terminal's cells divided by their width, if it can
+pub type AspectSource = Box<dyn FnMut() -> Option<f64> + Send>;
+
+// Stops the program until it is continued, then returns the terminal size
+pub type Suspend = Box<dyn FnMut() -> io::Result<(u16, u16)> + Send>;
+
+// Watch the local terminal, which announces size changes with SIGWINCH
+pub fn watch_resize() -> io::Result<SizeSource> {
+    let resized = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGWINCH, Arc::clone(&resized))?;
+    Ok(Box::new(move || {
+        if resized.swap(false, Ordering::Relaxed) {
+            termion::terminal_size().ok()
+        } else {
+            None
+        }
+    }))
+}
+
+// Height of the local terminal's cells divided by their width, if it reports its size in pixels
+pub fn cell_aspect() -> Option<f64> {
+    let (columns, rows) = termion::terminal_size().ok()?;
+    let (width, height) = termion::terminal_size_pixels().ok()?;
+    if columns == 0 || rows == 0 || width == 0 || height == 0 {
+        return None;
+    }
+    Some((f64::from(height) / f64::from(rows)) / (f64::from(width) / f64::from(columns)))
+}
+
+// Hand the local terminal back to the shell and stop like Ctrl-Z does in cooked mode. Once the
+// process is continued, switch back to raw mode and the alternate screen.
+pub fn suspend() -> io::Result<(u16, u16)> {
+    // SAFETY: tcgetattr fills in the zeroed struct, which is only used if the call succeeds
+    let mut raw: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDOUT_FILENO, &mut raw) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    crash::restore_terminal();
+    // Returns after SIGCONT
+    signal_hook::low_level::raise(SIGTSTP)?;
+    // SAFETY: `raw` holds the settings read above
+    if unsafe { libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, &raw) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut stdout = io::stdout();
+    write!(stdout, "{ToAlternateScreen}{}", cursor::Hide)?;
+    stdout.flush()?;
+    termion::terminal_size()
+}
@@ -0,0 +1,205 @@
+use crate::game_instance::{Direction, GridCell, Snake};
+use crate::glyphs::GlyphSet;
+use crate::parser::SnakeSkin;
+use crate::theme::{Color, Theme};
+
+const BLACK: Color = Color::Rgb(0, 0, 0);
+// Filler glyph of each player's snake in the classic skin
+const SNAKE_BODY_CHARS: [char; 8] = ['a', 'o', 'x', '=', '+', '~', '#', '*'];
+// How far the tail of a gradient snake fades towards black
+const GRADIENT_FADE: f64 = 0.7;
+const STRIPE_SHADE: f64 = 0.45;
+
+// How one segment of a snake is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub glyph: char,
+    // Fills the rest of a cell wider than one column
+    pub pad: char,
+    pub fg: Color,
+    // `None` leaves the terminal's background, for glyphs that draw the body themselves
+    pub bg: Option<Color>,
+}
+
+// The segments of `snake`, head first, in `skin` and the colours of `player`
+pub fn segments(
+    skin: SnakeSkin,
+    theme: &Theme,
+    glyphs: GlyphSet,
+    snake: &Snake,
+    player: usize,
+) -> Vec<Segment> {
+    let color = theme.snake(player);
+    let taper = glyphs.taper();
+    let len = snake.body.len();
+    (0..len)
+        .map(|index| {
+            let solid = |glyph, bg| Segment {
+                glyph,
+                pad: ' ',
+                fg: theme.snake_glyph,
+                bg: Some(bg),
+            };
+            let head = (index == 0).then(|| glyphs.arrow(heading(snake)));
+            match skin {
+                SnakeSkin::Classic => {
+                    let glyph = match index {
+                        0 => 'S',
+                        num if num == len - 1 => 'e',
+                        1 => 'n',
+                        num if num == len - 2 => 'k',
+                        _ => SNAKE_BODY_CHARS[player % SNAKE_BODY_CHARS.len()],
+                    };
+                    solid(glyph, color)
+                }
+                SnakeSkin::Gradient => {
+                    let fade = GRADIENT_FADE * index as f64 / (len - 1).max(1) as f64;
+                    solid(head.unwrap_or(' '), color.mix(BLACK, fade))
+                }
+                SnakeSkin::Stripes => {
+                    let bg = if index / 2 % 2 == 0 {
+                        color
+                    } else {
+                        color.mix(BLACK, STRIPE_SHADE)
+                    };
+                    solid(head.unwrap_or(' '), bg)
+                }
+                SnakeSkin::Tapered => {
+                    let from_tail = len - 1 - index;
+                    if index > 0 && from_tail < taper.len() {
+                        let glyph = taper[taper.len() - 1 - from_tail];
+                        Segment {
+                            glyph,
+                            pad: glyph,
+                            fg: color,
+                            bg: None,
+                        }
+                    } else {
+                        solid(head.unwrap_or(' '), color)
+                    }
+                }
+                SnakeSkin::Pipes => {
+                    let body = &snake.body;
+                    let toward_head = index.checked_sub(1).map(|i| side(&body[index], &body[i]));
+                    let toward_tail = body.get(index + 1).map(|next| side(&body[index], next));
+                    let glyph = head.unwrap_or_else(|| glyphs.pipe(toward_head, toward_tail));
+                    let joins_right = [toward_head, toward_tail].contains(&Some(Direction::Right));
+                    Segment {
+                        glyph,
+                        pad: if joins_right {
+                            glyphs.horizontal()
+                        } else {
+                            ' '
+                        },
+                        fg: color,
+                        bg: None,
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+// Where the snake last moved
+fn heading(snake: &Snake) -> Direction {
+    match (snake.body.front(), snake.body.get(1)) {
+        (Some(head), Some(neck)) => side(neck, head),
+        _ => snake.direction,
+    }
+}
+
+// Side of `from` that the neighbouring segment `to` is on. Snakes wrap around the board, so a
+// neighbour far away is on the other side.
+fn side(from: &GridCell, to: &GridCell) -> Direction {
+    let (dx, dy) = (
+        i32::from(to.x) - i32::from(from.x),
+        i32::from(to.y) - i32::from(from.y),
+    );
+    match (dx, dy) {
+        (1, _) | (..=-2, _) => Direction::Right,
+        (-1, _) | (2.., _) => Direction::Left,
+        (_, 1) | (_, ..=-2) => Direction::Down,
+        _ => Direction::Up,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    fn snake(cells: &[(u16, u16)], direction: Direction) -> Snake {
+        Snake {
+            body: cells
+                .iter()
+                .map(|&(x, y)| GridCell { x, y })
+                .collect::<VecDeque<_>>(),
+            old_tail: None,
+            direction,
+            alive: true,
+            food_eaten: 0,
+            death: None,
+        }
+    }
+
+    fn glyphs(skin: SnakeSkin, snake: &Snake) -> String {
+        segments(skin, &Theme::classic(), GlyphSet::Unicode, snake, 0)
+            .iter()
+            .map(|segment| segment.glyph)
+            .collect()
+    }
+
+    #[test]
+    fn pipes_turn_corners_across_the_wrap() {
+        // Moved right off the right edge, coming up from below
+        let right = snake(&[(1, 5), (10, 5), (10, 6), (10, 7)], Direction::Right);
+        assert_eq!(
+            glyphs(SnakeSkin::Pipes, &right),
+            "\u{25B6}\u{250C}\u{2502}\u{2575}"
+        );
+        // Moved up off the top edge, coming from the right
+        let up = snake(&[(3, 10), (3, 1), (4, 1), (5, 1)], Direction::Up);
+        assert_eq!(
+            glyphs(SnakeSkin::Pipes, &up),
+            "\u{25B2}\u{2514}\u{2500}\u{2574}"
+        );
+    }
+
+    #[test]
+    fn pipes_fill_wide_cells_only_towards_the_right() {
+        let body = snake(&[(4, 1), (3, 1), (2, 1), (2, 2)], Direction::Right);
+        let segments = segments(
+            SnakeSkin::Pipes,
+            &Theme::classic(),
+            GlyphSet::Unicode,
+            &body,
+            0,
+        );
+        let pads: Vec<char> = segments.iter().map(|segment| segment.pad).collect();
+        assert_eq!(pads, [' ', '\u{2500}', '\u{2500}', ' ']);
+    }
+
+    #[test]
+    fn short_snakes_taper_behind_the_head() {
+        let taper = GlyphSet::Unicode.taper();
+        let cells = [(5, 1), (4, 1), (3, 1), (2, 1), (1, 1)];
+        let tapered = |len| glyphs(SnakeSkin::Tapered, &snake(&cells[..len], Direction::Right));
+        assert_eq!(tapered(1), "\u{25B6}");
+        assert_eq!(tapered(2), format!("\u{25B6}{}", taper[2]));
+        assert_eq!(tapered(3), format!("\u{25B6}{}{}", taper[1], taper[2]));
+        let full = format!("\u{25B6} {}{}{}", taper[0], taper[1], taper[2]);
+        assert_eq!(tapered(5), full);
+
+        // The tapered end is drawn in the snake's colour on the terminal's background
+        let body = snake(&cells[..2], Direction::Right);
+        let tail = segments(
+            SnakeSkin::Tapered,
+            &Theme::classic(),
+            GlyphSet::Unicode,
+            &body,
+            1,
+        )[1];
+        assert_eq!(tail.fg, Theme::classic().snake(1));
+        assert_eq!((tail.pad, tail.bg), (taper[2], None));
+    }
+}
@@ -0,0 +1,365 @@
+use std::error::Error;
+use std::fs;
+use std::io::{self, stdin, stdout, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::screen::IntoAlternateScreen;
+use termion::terminal_size;
+
+use crate::error::GameError;
+use crate::game_input::{GameInput, KeyPress};
+use crate::game_instance::GameInstance;
+use crate::game_output::GameOutput;
+use crate::net_protocol::ServerMessage;
+use crate::remote_game::RemoteGame;
+
+// Watchers that stop reading for this long are dropped
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+// Messages queued for a watcher that have not been sent yet. Watchers are written to from
+// their own threads, and one this far behind is dropped, so none of them can stall the game.
+const OUTBOX_SIZE: usize = 64;
+const UNIX_PREFIX: &str = "unix:";
+
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener, String),
+}
+
+// Streams the state of a running game to read-only watchers.
+// Addresses are either `host:port` or `unix:<path>`.
+pub struct SpectatorServer {
+    listener: Listener,
+    // Lines for the writer thread of every watcher
+    watchers: Vec<SyncSender<String>>,
+}
+
+impl SpectatorServer {
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        let listener = match addr.strip_prefix(UNIX_PREFIX) {
+            Some(path) => {
+                // Replace a socket left behind by an earlier game, but never another kind of file
+                if fs::metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+                    fs::remove_file(path)?;
+                }
+                let listener = UnixListener::bind(path)?;
+                listener.set_nonblocking(true)?;
+                Listener::Unix(listener, path.to_string())
+            }
+            None => {
+                let listener = TcpListener::bind(addr)?;
+                listener.set_nonblocking(true)?;
+                Listener::Tcp(listener)
+            }
+        };
+        Ok(Self {
+            listener,
+            watchers: Vec::new(),
+        })
+    }
+
+    // Accept new watchers, sending them the running round (if any) so they can start drawing
+    pub fn accept(&mut self, running: Option<&GameInstance>) {
+        while let Some(stream) = self.accept_one() {
+            let watcher = Self::spawn_writer(stream);
+            if let Some(instance) = running {
+                let snapshot = [
+                    ServerMessage::spectate(instance),
+                    ServerMessage::snapshot(instance),
+                ];
+                if snapshot
+                    .iter()
+                    .any(|message| watcher.try_send(message.encode()).is_err())
+                {
+                    continue;
+                }
+            }
+            self.watchers.push(watcher);
+        }
+    }
+
+    // Send the lines queued for a watcher until its connection breaks or times out
+    fn spawn_writer(mut stream: Box<dyn Write + Send>) -> SyncSender<String> {
+        let (outbox, lines) = mpsc::sync_channel::<String>(OUTBOX_SIZE);
+        thread::spawn(move || {
+            for line in lines {
+                if writeln!(stream, "{line}").is_err() {
+                    return;
+                }
+            }
+        });
+        outbox
+    }
+
+    fn accept_one(&self) -> Option<Box<dyn Write + Send>> {
+        match &self.listener {
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept().ok()?;
+                stream.set_nonblocking(false).ok()?;
+                stream.set_nodelay(true).ok()?;
+                stream.set_write_timeout(Some(WRITE_TIMEOUT)).ok()?;
+                Some(Box::new(stream))
+            }
+            Listener::Unix(listener, _) => {
+                let (stream, _) = listener.accept().ok()?;
+                stream.set_nonblocking(false).ok()?;
+                stream.set_write_timeout(Some(WRITE_TIMEOUT)).ok()?;
+                Some(Box::new(stream))
+            }
+        }
+    }
+
+    // Queue a message for every watcher, dropping the ones whose connection broke or who fell
+    // too far behind
+    pub fn broadcast(&mut self, message: &ServerMessage) {
+        let line = message.encode();
+        self.watchers
+            .retain(|watcher| watcher.try_send(line.clone()).is_ok());
+    }
+}
+
+impl Drop for SpectatorServer {
+    fn drop(&mut self) {
+        if let Listener::Unix(_, path) = &self.listener {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// Read-only view of a game streamed by a `SpectatorServer`
+pub struct Watcher {
+    messages: Receiver<ServerMessage>,
+    game: Option<RemoteGame>,
+    input: GameInput,
+    output: GameOutput,
+    terminal_size: fn() -> io::Result<(u16, u16)>,
+}
+
+impl Watcher {
+    pub fn connect(addr: &str) -> Result<Self, Box<dyn Error>> {
+        let messages = Self::subscribe(addr)?;
+        // Watchers only need the quit key
+        let input = GameInput::new(stdin().keys(), &[]);
+        let output = stdout().into_raw_mode()?.into_alternate_screen()?;
+        let output = GameOutput::new(output);
+        Ok(Self {
+            messages,
+            game: None,
+            input,
+            output,
+            terminal_size,
+        })
+    }
+
+    // Connect to a `SpectatorServer` and forward its messages from a background thread. The
+    // channel closes with the connection.
+    fn subscribe(addr: &str) -> io::Result<Receiver<ServerMessage>> {
+        let stream: Box<dyn Read + Send> = match addr.strip_prefix(UNIX_PREFIX) {
+            Some(path) => Box::new(UnixStream::connect(path)?),
+            None => Box::new(TcpStream::connect(addr)?),
+        };
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else { break };
+                if let Some(message) = ServerMessage::decode(&line) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(messages)
+    }
+
+    pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
+        let result = self.main_loop();
+        self.output.clear_screen()?;
+        self.output.show_cursor()?;
+        self.output.render()?;
+        result
+    }
+
+    fn main_loop(&mut self) -> Result<(), Box<dyn Error>> {
+        self.output.clear_screen()?;
+        self.output.draw_spectator_message()?;
+        self.output.render()?;
+        loop {
+            loop {
+                match self.messages.try_recv() {
+                    Ok(message) => self.handle_message(message)?,
+                    Err(TryRecvError::Empty) => break,
+                    // The game has ended
+                    Err(TryRecvError::Disconnected) => return Ok(()),
+                }
+            }
+            if self.input.get_keypresses().contains(&KeyPress::Quit) {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn handle_message(&mut self, message: ServerMessage) -> Result<(), GameError> {
+        match message {
+            // A new round may use a different board, so size it to this terminal again
+            ServerMessage::Spectate { width, height, .. } => {
+                self.game = RemoteGame::scaled(width, height, (self.terminal_size)()?);
+            }
+            ServerMessage::Snapshot { food, snakes } => {
+                if let Some(game) = &mut self.game {
+                    game.apply_snapshot(&food, &snakes);
+                    game.draw_board(&mut self.output)?;
+                    self.output.render()?;
+                }
+            }
+            ServerMessage::Tick { food, snakes } => {
+                if let Some(game) = &mut self.game {
+                    game.apply_tick(&food, &snakes, &mut self.output)?;
+                    game.draw_snakes(&mut self.output)?;
+                    self.output.draw_food(&game.food)?;
+                    self.output.render()?;
+                }
+            }
+            ServerMessage::Over { winner } => {
+                if let Some(game) = self.game.take() {
+                    let title = match winner {
+                        Some(player) => format!("player {}|wins!", player + 1),
+                        None => String::from("game|over!"),
+                    };
+                    self.output.clear_screen()?;
+                    self.output.draw_winner_message(&title, &game.results())?;
+                    self.output.render()?;
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use crate::game_instance::GameGrid;
+
+    // Output the test can look at while the watcher owns it
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedOutput {
+        fn take(&self) -> Vec<u8> {
+            std::mem::take(&mut self.0.lock().unwrap())
+        }
+    }
+
+    fn local_addr(server: &SpectatorServer) -> String {
+        match &server.listener {
+            Listener::Tcp(listener) => listener.local_addr().unwrap().to_string(),
+            Listener::Unix(_, path) => format!("{UNIX_PREFIX}{path}"),
+        }
+    }
+
+    // Accept watchers until one has connected
+    fn accept_watcher(server: &mut SpectatorServer, running: Option<&GameInstance>) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.watchers.is_empty() {
+            assert!(Instant::now() < deadline, "watcher did not connect");
+            server.accept(running);
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn a_watcher_joining_a_running_round_draws_its_frames() {
+        let mut server = SpectatorServer::bind("127.0.0.1:0").unwrap();
+        let mut instance = GameInstance::with_players(GameGrid::with_dimensions(20, 10), 2);
+        let output = SharedOutput::default();
+        let mut watcher = Watcher {
+            messages: Watcher::subscribe(&local_addr(&server)).unwrap(),
+            game: None,
+            input: GameInput::new(std::iter::empty(), &[]),
+            output: GameOutput::new(output.clone()),
+            terminal_size: || Ok((80, 24)),
+        };
+        accept_watcher(&mut server, Some(&instance));
+
+        let receive = |watcher: &mut Watcher| {
+            let message = watcher
+                .messages
+                .recv_timeout(Duration::from_secs(5))
+                .unwrap();
+            watcher.handle_message(message.clone()).unwrap();
+            message
+        };
+        assert!(matches!(
+            receive(&mut watcher),
+            ServerMessage::Spectate {
+                players: 2,
+                width: 20,
+                height: 10
+            }
+        ));
+        assert!(matches!(
+            receive(&mut watcher),
+            ServerMessage::Snapshot { .. }
+        ));
+        let game = watcher.game.as_ref().unwrap();
+        assert_eq!(game.snakes.len(), 2);
+        assert!(!output.take().is_empty(), "the snapshot was not drawn");
+
+        let alive: Vec<bool> = instance.snakes.iter().map(|snake| snake.alive).collect();
+        instance.game_cycle();
+        server.broadcast(&ServerMessage::tick(&instance, &alive));
+        assert!(matches!(receive(&mut watcher), ServerMessage::Tick { .. }));
+        let game = watcher.game.as_ref().unwrap();
+        for (local, remote) in game.snakes.iter().zip(&instance.snakes) {
+            assert_eq!(local.body.len(), remote.body.len());
+        }
+        assert!(!output.take().is_empty(), "the tick was not drawn");
+
+        server.broadcast(&ServerMessage::Over { winner: Some(1) });
+        assert_eq!(
+            receive(&mut watcher),
+            ServerMessage::Over { winner: Some(1) }
+        );
+        assert!(watcher.game.is_none());
+
+        // The watcher's connection closes with the server
+        drop(server);
+        let closed = watcher.messages.recv_timeout(Duration::from_secs(5));
+        assert_eq!(closed, Err(mpsc::RecvTimeoutError::Disconnected));
+    }
+
+    #[test]
+    fn a_watcher_that_falls_behind_is_dropped() {
+        let mut server = SpectatorServer::bind("127.0.0.1:0").unwrap();
+        // Nothing takes messages out of this outbox, like a writer stuck on a stalled connection
+        let (outbox, _lines) = mpsc::sync_channel(1);
+        server.watchers.push(outbox);
+        server.broadcast(&ServerMessage::Full);
+        assert_eq!(server.watchers.len(), 1);
+        server.broadcast(&ServerMessage::Full);
+        assert!(server.watchers.is_empty());
+    }
+}
@@ -0,0 +1,531 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use termion::input::TermRead;
+use termion::screen::{ToAlternateScreen, ToMainScreen};
+
+use crate::game::{check_terminal_size, Game};
+use crate::game_input::GameInput;
+use crate::game_output::GameOutput;
+use crate::glyphs::GlyphSet;
+use crate::parser::ArgsParser;
+use crate::theme::{ColorDepth, Theme};
+
+// Telnet commands and options, see RFC 854, 857, 858 and 1073
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const OPT_ECHO: u8 = 1;
+const OPT_SUPPRESS_GO_AHEAD: u8 = 3;
+const OPT_NAWS: u8 = 31;
+
+// Size used when the client does not report its window size in time
+const DEFAULT_SIZE: (u16, u16) = (80, 24);
+const NAWS_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub struct TelnetServer {
+    listener: TcpListener,
+    args: ArgsParser,
+    theme: Theme,
+    // Connections beyond this many running sessions are turned away
+    max_sessions: usize,
+    sessions: Arc<AtomicUsize>,
+}
+
+impl TelnetServer {
+    pub fn bind(addr: &str, args: ArgsParser, max_sessions: usize) -> io::Result<Self> {
+        let theme = Theme::load(&args.theme)?;
+        let listener = TcpListener::bind(addr)?;
+        Ok(Self {
+            listener,
+            args,
+            theme,
+            max_sessions,
+            sessions: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    // Run an independent game for every connection, until the process is stopped
+    pub fn run(&self) -> io::Result<()> {
+        println!(
+            "Accepting telnet connections on {}",
+            self.listener.local_addr()?
+        );
+        for stream in self.listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    eprintln!("Failed to accept a telnet connection: {err}");
+                    continue;
+                }
+            };
+            let Some(slot) = SessionSlot::take(&self.sessions, self.max_sessions) else {
+                let _ = write!(stream, "The server is full, try again later.\r\n");
+                let _ = stream.shutdown(std::net::Shutdown::Both);
+                continue;
+            };
+            let args = self.args.clone();
+            let theme = self.theme.clone();
+            thread::spawn(move || {
+                let _slot = slot;
+                let peer = stream.peer_addr().ok();
+                if let Err(err) = run_session(stream, args, theme) {
+                    eprintln!("Telnet session {peer:?} ended with an error: {err}");
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+// One of the running sessions counted towards the limit, given back when dropped
+struct SessionSlot(Arc<AtomicUsize>);
+
+impl SessionSlot {
+    fn take(sessions: &Arc<AtomicUsize>, max_sessions: usize) -> Option<Self> {
+        sessions
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |running| {
+                (running < max_sessions).then_some(running + 1)
+            })
+            .ok()?;
+        Some(Self(Arc::clone(sessions)))
+    }
+}
+
+impl Drop for SessionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn run_session(stream: TcpStream, args: ArgsParser, theme: Theme) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut output = stream.try_clone()?;
+    // Ask for character mode: the server echoes (that is, nothing), no go-aheads, and window size
+    output.write_all(&[
+        IAC,
+        WILL,
+        OPT_ECHO,
+        IAC,
+        WILL,
+        OPT_SUPPRESS_GO_AHEAD,
+        IAC,
+        DO,
+        OPT_SUPPRESS_GO_AHEAD,
+        IAC,
+        DO,
+        OPT_NAWS,
+    ])?;
+
+    let window_size = Arc::new(Mutex::new(None));
+    // Start reading right away to take in the window size. Read errors and hang-ups end the
+    // keys, which the game sees as the quit key.
+    let keys = TelnetReader::new(stream.try_clone()?, Arc::clone(&window_size)).keys();
+    let input = GameInput::new(keys, &args.key_schemes());
+
+    // Give the client a moment to report its window size
+    let start = Instant::now();
+    let terminal_size = loop {
+        if let Some(size) = *window_size.lock().unwrap() {
+            break size;
+        }
+        if start.elapsed() >= NAWS_TIMEOUT {
+            break DEFAULT_SIZE;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    if let Err(err) = check_terminal_size(&args, terminal_size) {
+        write!(output, "{err}\r\n")?;
+        return stream.shutdown(std::net::Shutdown::Both);
+    }
+
+    write!(output, "{ToAlternateScreen}")?;
+    let mut game_output = GameOutput::new(HangupWriter(stream.try_clone()?));
+    // The server's environment says nothing about the client's terminal, so unless told
+    // otherwise assume the 256 colours and Unicode nearly every terminal has
+    let depth = args.color.depth().unwrap_or(ColorDepth::Ansi256);
+    game_output.set_theme(theme, depth);
+    game_output.set_glyphs(args.glyphs.glyphs().unwrap_or(GlyphSet::Unicode));
+    let mut game = Game::new(args, input, game_output, terminal_size);
+    // Clients report window size changes with another NAWS subnegotiation
+    game.set_size_source(Box::new(move || *window_size.lock().unwrap()));
+    // Writes never fail, since `HangupWriter` drops them once the client is gone
+    let _ = game.run();
+    // The client may already be gone, in which case there is nothing left to restore
+    let _ = write!(output, "{ToMainScreen}").and_then(|_| output.flush());
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+    Ok(())
+}
+
+// Drops writes to a connection the client closed; the game quits once the input notices
+struct HangupWriter(TcpStream);
+
+impl Write for HangupWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.0.write(buf).unwrap_or(buf.len()))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let _ = self.0.flush();
+        Ok(())
+    }
+}
+
+// Reads the data bytes of a telnet connection, handling commands and window size reports
+struct TelnetReader {
+    stream: TcpStream,
+    window_size: Arc<Mutex<Option<(u16, u16)>>>,
+    // Bytes of a command that was split across reads
+    pending: Vec<u8>,
+    // The last read ended with a carriage return, whose NUL or line feed may start the next
+    after_cr: bool,
+    // Data that did not fit in the caller's buffer yet
+    data: VecDeque<u8>,
+}
+
+impl TelnetReader {
+    fn new(stream: TcpStream, window_size: Arc<Mutex<Option<(u16, u16)>>>) -> Self {
+        Self {
+            stream,
+            window_size,
+            pending: Vec::new(),
+            after_cr: false,
+            data: VecDeque::new(),
+        }
+    }
+
+    // Strip commands from `bytes`, appending data to `data`. Returns the number of bytes
+    // consumed; an incomplete command at the end is left for the next read.
+    fn filter(&mut self, bytes: &[u8], data: &mut Vec<u8>) -> usize {
+        let mut i = 0;
+        if std::mem::take(&mut self.after_cr) && matches!(bytes.first(), Some(&(0 | b'\n'))) {
+            i = 1;
+        }
+        while i < bytes.len() {
+            match bytes[i] {
+                IAC => match bytes.get(i + 1) {
+                    None => return i,
+                    Some(&IAC) => {
+                        data.push(IAC);
+                        i += 2;
+                    }
+                    Some(&(WILL | WONT | DO | DONT)) => {
+                        if i + 2 >= bytes.len() {
+                            return i;
+                        }
+                        i += 3;
+                    }
+                    Some(&SB) => {
+                        let Some(end) = subnegotiation_end(&bytes[i + 2..]) else {
+                            return i;
+                        };
+                        self.subnegotiation(&bytes[i + 2..i + 2 + end]);
+                        i += end + 4;
+                    }
+                    Some(_) => i += 2,
+                },
+                // A carriage return is followed by a NUL or line feed that carries no data
+                b'\r' => {
+                    data.push(b'\r');
+                    match bytes.get(i + 1) {
+                        None => {
+                            self.after_cr = true;
+                            return i + 1;
+                        }
+                        Some(&(0 | b'\n')) => i += 2,
+                        Some(_) => i += 1,
+                    }
+                }
+                byte => {
+                    data.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        i
+    }
+
+    // Take in the data of a new read
+    fn feed(&mut self, chunk: &[u8]) {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(chunk);
+        let mut data = Vec::new();
+        let consumed = self.filter(&bytes, &mut data);
+        self.pending = bytes[consumed..].to_vec();
+        self.data.extend(data);
+    }
+
+    fn subnegotiation(&mut self, payload: &[u8]) {
+        // Window sizes equal to IAC are sent doubled
+        let mut unescaped = Vec::with_capacity(payload.len());
+        let mut bytes = payload.iter();
+        while let Some(&byte) = bytes.next() {
+            if byte == IAC {
+                bytes.next();
+            }
+            unescaped.push(byte);
+        }
+        if let [OPT_NAWS, w1, w2, h1, h2] = unescaped[..] {
+            let size = (u16::from_be_bytes([w1, w2]), u16::from_be_bytes([h1, h2]));
+            if size.0 > 0 && size.1 > 0 {
+                *self.window_size.lock().unwrap() = Some(size);
+            }
+        }
+    }
+}
+
+// Position of the IAC SE ending a subnegotiation, skipping IACs escaped in its data
+fn subnegotiation_end(bytes: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        match (bytes[i], bytes[i + 1]) {
+            (IAC, SE) => return Some(i),
+            (IAC, _) => i += 2,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+impl Read for TelnetReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Keep reading until there is data, since returning 0 bytes means end of stream
+        while self.data.is_empty() {
+            let mut chunk = [0; 512];
+            let count = self.stream.read(&mut chunk)?;
+            if count == 0 {
+                return Ok(0);
+            }
+            self.feed(&chunk[..count]);
+        }
+        let len = self.data.len().min(buf.len());
+        for (slot, byte) in buf.iter_mut().zip(self.data.drain(..len)) {
+            *slot = byte;
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A reader on one end of a loopback connection, and the client's end
+    fn connection() -> (TelnetReader, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let reader = TelnetReader::new(stream, Arc::new(Mutex::new(None)));
+        (reader, client)
+    }
+
+    // Data the reader got out of `reads`, each handled as a separate read
+    fn feed(reader: &mut TelnetReader, reads: &[&[u8]]) -> Vec<u8> {
+        for bytes in reads {
+            reader.feed(bytes);
+        }
+        reader.data.drain(..).collect()
+    }
+
+    fn window_size(reader: &TelnetReader) -> Option<(u16, u16)> {
+        *reader.window_size.lock().unwrap()
+    }
+
+    #[test]
+    fn doubled_iac_is_data() {
+        let (mut reader, _client) = connection();
+        assert_eq!(
+            feed(&mut reader, &[&[b'a', IAC, IAC, b'b']]),
+            [b'a', IAC, b'b']
+        );
+    }
+
+    #[test]
+    fn carriage_returns_drop_their_nul_or_line_feed() {
+        let (mut reader, _client) = connection();
+        assert_eq!(feed(&mut reader, &[b"a\r\0b\r\nc\rd"]), b"a\rb\rc\rd");
+        assert_eq!(
+            feed(&mut reader, &[b"e\r", b"\0f", b"\r", b"\ng"]),
+            b"e\rf\rg"
+        );
+    }
+
+    #[test]
+    fn commands_split_across_reads_are_skipped() {
+        let (mut reader, _client) = connection();
+        let reads: [&[u8]; 3] = [
+            &[b'x', IAC],
+            &[WILL],
+            &[OPT_ECHO, b'y', IAC, DO, OPT_NAWS, b'z'],
+        ];
+        assert_eq!(feed(&mut reader, &reads), b"xyz");
+        assert!(reader.pending.is_empty());
+    }
+
+    #[test]
+    fn window_size_split_across_reads_is_read() {
+        let (mut reader, _client) = connection();
+        let reads: [&[u8]; 2] = [&[b'a', IAC, SB, OPT_NAWS, 0, 80], &[0, 24, IAC, SE, b'b']];
+        assert_eq!(feed(&mut reader, &reads), b"ab");
+        assert_eq!(window_size(&reader), Some((80, 24)));
+    }
+
+    #[test]
+    fn window_size_of_255_is_unescaped() {
+        let (mut reader, _client) = connection();
+        let report = [IAC, SB, OPT_NAWS, 0, IAC, IAC, 0, 40, IAC, SE];
+        assert_eq!(feed(&mut reader, &[&report]), b"");
+        assert_eq!(window_size(&reader), Some((255, 40)));
+    }
+
+    #[test]
+    fn zero_window_size_is_ignored() {
+        let (mut reader, _client) = connection();
+        feed(&mut reader, &[&[IAC, SB, OPT_NAWS, 0, 80, 0, 24, IAC, SE]]);
+        feed(&mut reader, &[&[IAC, SB, OPT_NAWS, 0, 0, 0, 24, IAC, SE]]);
+        assert_eq!(window_size(&reader), Some((80, 24)));
+    }
+
+    #[test]
+    fn reads_return_only_data() {
+        let (mut reader, mut client) = connection();
+        client
+            .write_all(&[IAC, WILL, OPT_NAWS, b'h', b'i'])
+            .unwrap();
+        let mut buf = [0; 8];
+        let count = reader.read(&mut buf).unwrap();
+        assert_eq!(&buf[..count], b"hi");
+    }
+
+    // Run a telnet server on a free port, returning its address and running sessions
+    fn start_server(max_sessions: usize) -> (std::net::SocketAddr, Arc<AtomicUsize>) {
+        let args = <ArgsParser as clap::Parser>::parse_from(["rs_snake"]);
+        let server = TelnetServer::bind("127.0.0.1:0", args, max_sessions).unwrap();
+        let addr = server.listener.local_addr().unwrap();
+        let sessions = Arc::clone(&server.sessions);
+        thread::spawn(move || server.run());
+        (addr, sessions)
+    }
+
+    // Connect and check the options the server asks for
+    fn negotiate(addr: std::net::SocketAddr) -> TcpStream {
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut negotiation = [0; 12];
+        client.read_exact(&mut negotiation).unwrap();
+        assert_eq!(
+            negotiation,
+            [
+                IAC,
+                WILL,
+                OPT_ECHO,
+                IAC,
+                WILL,
+                OPT_SUPPRESS_GO_AHEAD,
+                IAC,
+                DO,
+                OPT_SUPPRESS_GO_AHEAD,
+                IAC,
+                DO,
+                OPT_NAWS
+            ]
+        );
+        client
+    }
+
+    // Everything the server sends for `duration`, or until it closes the connection
+    fn read_for(client: &mut TcpStream, duration: Duration) -> (Vec<u8>, bool) {
+        let start = Instant::now();
+        let mut output = Vec::new();
+        let mut chunk = [0; 4096];
+        while start.elapsed() < duration {
+            match client.read(&mut chunk) {
+                Ok(0) => return (output, true),
+                Ok(count) => output.extend_from_slice(&chunk[..count]),
+                Err(err) => panic!("read failed: {err}"),
+            }
+        }
+        (output, false)
+    }
+
+    // Largest row and column the cursor is moved to
+    fn drawn_area(output: &[u8]) -> (u16, u16) {
+        let text = String::from_utf8_lossy(output);
+        let mut area = (0, 0);
+        for sequence in text.split("\x1b[").skip(1) {
+            let Some((position, _)) = sequence.split_once('H') else {
+                continue;
+            };
+            if let Some((row, column)) = position.split_once(';') {
+                if let (Ok(row), Ok(column)) = (row.parse(), column.parse()) {
+                    area = (area.0.max(row), area.1.max(column));
+                }
+            }
+        }
+        area
+    }
+
+    fn wait_for_sessions(sessions: &AtomicUsize, count: usize) {
+        let start = Instant::now();
+        while sessions.load(Ordering::SeqCst) != count {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "session did not end"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn a_session_draws_for_the_reported_window_size_and_quits() {
+        let (addr, sessions) = start_server(4);
+        let mut client = negotiate(addr);
+        // Larger than the default size, so drawing past it shows the report was used
+        client
+            .write_all(&[IAC, SB, OPT_NAWS, 0, 100, 0, 30, IAC, SE])
+            .unwrap();
+        let (output, closed) = read_for(&mut client, Duration::from_millis(500));
+        assert!(!closed);
+        let (rows, columns) = drawn_area(&output);
+        assert!(rows <= 30 && columns <= 100, "drew up to {rows}x{columns}");
+        assert!(columns > DEFAULT_SIZE.0, "drew up to {rows}x{columns}");
+        assert_eq!(sessions.load(Ordering::SeqCst), 1);
+
+        client.write_all(b"q").unwrap();
+        let (_, closed) = read_for(&mut client, Duration::from_secs(5));
+        assert!(closed);
+        wait_for_sessions(&sessions, 0);
+    }
+
+    #[test]
+    fn connections_beyond_the_limit_are_turned_away_until_a_session_ends() {
+        let (addr, sessions) = start_server(1);
+        let playing = negotiate(addr);
+
+        let mut turned_away = TcpStream::connect(addr).unwrap();
+        turned_away
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut message = String::new();
+        turned_away.read_to_string(&mut message).unwrap();
+        assert_eq!(message, "The server is full, try again later.\r\n");
+
+        // Hanging up ends the session and frees its slot
+        drop(playing);
+        wait_for_sessions(&sessions, 0);
+        let _next = negotiate(addr);
+        wait_for_sessions(&sessions, 1);
+    }
+}
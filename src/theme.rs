@@ -0,0 +1,483 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::settings::config_dir;
+
+// Names of the themes that need no file
+pub const THEMES: [&str; 5] = [
+    "classic",
+    "dark",
+    "solarized",
+    "high-contrast",
+    "colorblind-safe",
+];
+
+// Levels of the 6x6x6 colour cube in the 256-colour palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+// The 16 basic colours as xterm shows them by default
+const BASIC_COLORS: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    // Entry of the terminal's 256-colour palette, which the user may have changed
+    Ansi(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    fn rgb(&self) -> (u8, u8, u8) {
+        match *self {
+            Color::Rgb(r, g, b) => (r, g, b),
+            Color::Ansi(value @ 0..=15) => BASIC_COLORS[value as usize],
+            Color::Ansi(value @ 16..=231) => {
+                let value = value - 16;
+                (
+                    CUBE_LEVELS[(value / 36) as usize],
+                    CUBE_LEVELS[(value / 6 % 6) as usize],
+                    CUBE_LEVELS[(value % 6) as usize],
+                )
+            }
+            Color::Ansi(value) => {
+                let level = 8 + (value - 232) * 10;
+                (level, level, level)
+            }
+        }
+    }
+
+    // Nearest entry of the 256-colour palette, leaving out the basic colours the user may have
+    // changed
+    fn to_256(self) -> u8 {
+        let Color::Rgb(r, g, b) = self else {
+            return self.index();
+        };
+        let level = |c: u8| {
+            (0..CUBE_LEVELS.len())
+                .min_by_key(|&i| CUBE_LEVELS[i].abs_diff(c))
+                .unwrap() as u8
+        };
+        let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+        let average = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+        let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+        [cube, gray]
+            .into_iter()
+            .min_by_key(|&value| distance(Color::Ansi(value).rgb(), (r, g, b)))
+            .unwrap()
+    }
+
+    // Nearest of the 16 basic colours
+    fn to_16(self) -> u8 {
+        if let Color::Ansi(value @ 0..=15) = self {
+            return value;
+        }
+        let rgb = self.rgb();
+        (0..16)
+            .min_by_key(|&value| distance(BASIC_COLORS[value as usize], rgb))
+            .unwrap()
+    }
+
+    fn index(&self) -> u8 {
+        match *self {
+            Color::Ansi(value) => value,
+            Color::Rgb(..) => self.to_256(),
+        }
+    }
+
+    // Blend of this colour with `amount` of `other`, from 0 to 1
+    pub fn mix(self, other: Color, amount: f64) -> Color {
+        let (a, b) = (self.rgb(), other.rgb());
+        let channel =
+            |x: u8, y: u8| (f64::from(x) + (f64::from(y) - f64::from(x)) * amount).round() as u8;
+        Color::Rgb(channel(a.0, b.0), channel(a.1, b.1), channel(a.2, b.2))
+    }
+
+    // "#rrggbb" or a palette entry from 0 to 255
+    fn from_json(value: &Value) -> Option<Self> {
+        if let Some(value) = value.as_u64() {
+            return u8::try_from(value).ok().map(Color::Ansi);
+        }
+        let hex = value.as_str()?.strip_prefix('#')?;
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| u32::from(x.abs_diff(y)).pow(2);
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+// Colours the terminal can show
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    None,
+}
+
+impl ColorDepth {
+    // Read from the environment: `NO_COLOR` turns colours off, `COLORTERM` announces true
+    // colour and `TERM` names the terminal
+    pub fn detect() -> Self {
+        Self::detect_from(|name| env::var(name).ok())
+    }
+
+    // Detect from the environment variables `var` looks up
+    fn detect_from(var: impl Fn(&str) -> Option<String>) -> Self {
+        if var("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            return ColorDepth::None;
+        }
+        if let Some("truecolor" | "24bit") = var("COLORTERM").as_deref() {
+            return ColorDepth::TrueColor;
+        }
+        match var("TERM").unwrap_or_default() {
+            term if term.is_empty() || term == "dumb" => ColorDepth::None,
+            term if term.ends_with("-direct") => ColorDepth::TrueColor,
+            term if term.contains("256color") => ColorDepth::Ansi256,
+            _ => ColorDepth::Ansi16,
+        }
+    }
+
+    // Escape sequence setting the foreground to `color`, or the nearest colour the terminal has
+    pub fn fg(&self, color: Color) -> String {
+        self.sgr(color, 38, 30, 90)
+    }
+
+    pub fn bg(&self, color: Color) -> String {
+        self.sgr(color, 48, 40, 100)
+    }
+
+    fn sgr(&self, color: Color, extended: u8, basic: u8, bright: u8) -> String {
+        match (self, color) {
+            (ColorDepth::None, _) => String::new(),
+            (ColorDepth::TrueColor, Color::Rgb(r, g, b)) => {
+                format!("\x1b[{extended};2;{r};{g};{b}m")
+            }
+            (ColorDepth::TrueColor | ColorDepth::Ansi256, _) => {
+                format!("\x1b[{extended};5;{}m", color.index())
+            }
+            (ColorDepth::Ansi16, _) => match color.to_16() {
+                value @ 0..=7 => format!("\x1b[{}m", basic + value),
+                value => format!("\x1b[{}m", bright + value - 8),
+            },
+        }
+    }
+}
+
+// Colours of the board
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub border: Color,
+    // Background and glyph of the food
+    pub food: Color,
+    pub food_glyph: Color,
+    // Background and glyph of the cell a snake died on
+    pub fatal: Color,
+    pub fatal_glyph: Color,
+    // Letters on the snakes
+    pub snake_glyph: Color,
+    // Body of each player's snake, starting over for more players
+    pub snakes: Vec<Color>,
+}
+
+impl Theme {
+    pub fn classic() -> Self {
+        Self {
+            border: Color::Ansi(7),
+            food: Color::Ansi(1),
+            food_glyph: Color::Ansi(10),
+            fatal: Color::Ansi(9),
+            fatal_glyph: Color::Ansi(15),
+            snake_glyph: Color::Ansi(0),
+            snakes: [2, 4, 5, 6, 3, 208, 93, 250].map(Color::Ansi).to_vec(),
+        }
+    }
+
+    // A built-in theme by name
+    pub fn named(name: &str) -> Option<Self> {
+        let rgb = |colors: &[(u8, u8, u8)]| {
+            colors
+                .iter()
+                .map(|&(r, g, b)| Color::Rgb(r, g, b))
+                .collect()
+        };
+        let theme = match name {
+            "classic" => Self::classic(),
+            "dark" => Self {
+                border: Color::Rgb(88, 88, 88),
+                food: Color::Rgb(170, 40, 40),
+                food_glyph: Color::Rgb(255, 200, 120),
+                fatal: Color::Rgb(255, 85, 85),
+                fatal_glyph: Color::Rgb(255, 255, 255),
+                snake_glyph: Color::Rgb(20, 20, 20),
+                snakes: rgb(&[
+                    (78, 154, 6),
+                    (52, 101, 164),
+                    (117, 80, 123),
+                    (6, 152, 154),
+                    (196, 160, 0),
+                    (206, 92, 0),
+                    (92, 53, 204),
+                    (160, 160, 160),
+                ]),
+            },
+            "solarized" => Self {
+                border: Color::Rgb(88, 110, 117),
+                food: Color::Rgb(220, 50, 47),
+                food_glyph: Color::Rgb(253, 246, 227),
+                fatal: Color::Rgb(211, 54, 130),
+                fatal_glyph: Color::Rgb(253, 246, 227),
+                snake_glyph: Color::Rgb(0, 43, 54),
+                snakes: rgb(&[
+                    (133, 153, 0),
+                    (38, 139, 210),
+                    (108, 113, 196),
+                    (42, 161, 152),
+                    (181, 137, 0),
+                    (203, 75, 22),
+                    (211, 54, 130),
+                    (147, 161, 161),
+                ]),
+            },
+            "high-contrast" => Self {
+                border: Color::Rgb(255, 255, 255),
+                food: Color::Rgb(255, 0, 0),
+                food_glyph: Color::Rgb(255, 255, 0),
+                fatal: Color::Rgb(255, 0, 128),
+                fatal_glyph: Color::Rgb(255, 255, 255),
+                snake_glyph: Color::Rgb(0, 0, 0),
+                snakes: rgb(&[
+                    (0, 255, 0),
+                    (0, 170, 255),
+                    (255, 0, 255),
+                    (0, 255, 255),
+                    (255, 255, 0),
+                    (255, 128, 0),
+                    (170, 85, 255),
+                    (255, 255, 255),
+                ]),
+            },
+            // Okabe and Ito's palette, which stays distinct with every kind of colour blindness
+            "colorblind-safe" => Self {
+                border: Color::Rgb(153, 153, 153),
+                food: Color::Rgb(213, 94, 0),
+                food_glyph: Color::Rgb(255, 255, 255),
+                fatal: Color::Rgb(0, 0, 0),
+                fatal_glyph: Color::Rgb(255, 255, 255),
+                snake_glyph: Color::Rgb(0, 0, 0),
+                snakes: rgb(&[
+                    (0, 158, 115),
+                    (0, 114, 178),
+                    (230, 159, 0),
+                    (204, 121, 167),
+                    (86, 180, 233),
+                    (240, 228, 66),
+                    (255, 255, 255),
+                    (153, 153, 153),
+                ]),
+            },
+            _ => return None,
+        };
+        Some(theme)
+    }
+
+    // A built-in theme, a theme file in the `themes` directory of the config directory, or the
+    // theme file at a path
+    pub fn load(name: &str) -> io::Result<Self> {
+        if let Some(theme) = Self::named(name) {
+            return Ok(theme);
+        }
+        let installed = config_dir().map(|dir| dir.join("themes").join(format!("{name}.json")));
+        let path = match installed {
+            Some(path) if path.is_file() => path,
+            _ if Path::new(name).is_file() => Path::new(name).to_path_buf(),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!(
+                        "unknown theme '{name}', expected a file or one of {}",
+                        THEMES.join(", ")
+                    ),
+                ))
+            }
+        };
+        let text = fs::read_to_string(&path)?;
+        Self::from_json(&text).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid theme file {}: {err}", path.display()),
+            )
+        })
+    }
+
+    // A JSON object with the colours to change from the theme named by "base", or the classic
+    // one
+    fn from_json(text: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(text).map_err(|err| err.to_string())?;
+        let object = value.as_object().ok_or("expected an object")?;
+        let mut theme = match object.get("base") {
+            Some(base) => base
+                .as_str()
+                .and_then(Self::named)
+                .ok_or_else(|| format!("unknown base theme {base}"))?,
+            None => Self::classic(),
+        };
+        let color = |key: &str| -> Result<Option<Color>, String> {
+            object
+                .get(key)
+                .map(|value| {
+                    Color::from_json(value).ok_or_else(|| {
+                        format!("{key} is {value}, expected \"#rrggbb\" or 0 to 255")
+                    })
+                })
+                .transpose()
+        };
+        for (key, field) in [
+            ("border", &mut theme.border),
+            ("food", &mut theme.food),
+            ("food_glyph", &mut theme.food_glyph),
+            ("fatal", &mut theme.fatal),
+            ("fatal_glyph", &mut theme.fatal_glyph),
+            ("snake_glyph", &mut theme.snake_glyph),
+        ] {
+            if let Some(value) = color(key)? {
+                *field = value;
+            }
+        }
+        if let Some(snakes) = object.get("snakes") {
+            let snakes = snakes.as_array().ok_or("snakes is not an array")?;
+            theme.snakes = snakes
+                .iter()
+                .map(|value| {
+                    Color::from_json(value).ok_or(format!("snake colour {value} is invalid"))
+                })
+                .collect::<Result<_, _>>()?;
+            if theme.snakes.is_empty() {
+                return Err(String::from("snakes is empty"));
+            }
+        }
+        Ok(theme)
+    }
+
+    pub fn snake(&self, player: usize) -> Color {
+        self.snakes[player % self.snakes.len()]
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Colour depth detected with only the environment variables in `vars` set
+    fn detect(vars: &[(&str, &str)]) -> ColorDepth {
+        ColorDepth::detect_from(|name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn detects_the_colour_depth_from_the_environment() {
+        assert_eq!(detect(&[]), ColorDepth::None);
+        assert_eq!(detect(&[("TERM", "dumb")]), ColorDepth::None);
+        assert_eq!(detect(&[("TERM", "xterm")]), ColorDepth::Ansi16);
+        assert_eq!(detect(&[("TERM", "xterm-256color")]), ColorDepth::Ansi256);
+        assert_eq!(detect(&[("TERM", "xterm-direct")]), ColorDepth::TrueColor);
+        let truecolor = [("TERM", "xterm"), ("COLORTERM", "truecolor")];
+        assert_eq!(detect(&truecolor), ColorDepth::TrueColor);
+        let no_color = [
+            ("TERM", "xterm-256color"),
+            ("COLORTERM", "24bit"),
+            ("NO_COLOR", "1"),
+        ];
+        assert_eq!(detect(&no_color), ColorDepth::None);
+        // An empty NO_COLOR is as good as none
+        let empty = [("TERM", "xterm-256color"), ("NO_COLOR", "")];
+        assert_eq!(detect(&empty), ColorDepth::Ansi256);
+    }
+
+    #[test]
+    fn rgb_colours_map_to_the_nearest_palette_entry() {
+        // Corners of the colour cube, never the basic colours the user may have changed
+        assert_eq!(Color::Rgb(0, 0, 0).to_256(), 16);
+        assert_eq!(Color::Rgb(255, 255, 255).to_256(), 231);
+        assert_eq!(Color::Rgb(255, 0, 0).to_256(), 196);
+        assert_eq!(Color::Rgb(90, 140, 210).to_256(), 16 + 36 + 2 * 6 + 4);
+        // Grays between the cube's levels go to the gray ramp
+        assert_eq!(Color::Rgb(128, 128, 128).to_256(), 244);
+        assert_eq!(Color::Ansi(42).to_256(), 42);
+    }
+
+    #[test]
+    fn colours_map_to_the_nearest_basic_colour() {
+        assert_eq!(Color::Rgb(250, 10, 10).to_16(), 9);
+        assert_eq!(Color::Rgb(200, 0, 0).to_16(), 1);
+        assert_eq!(Color::Rgb(120, 120, 130).to_16(), 8);
+        assert_eq!(Color::Ansi(196).to_16(), 9);
+        assert_eq!(Color::Ansi(4).to_16(), 4);
+    }
+
+    #[test]
+    fn theme_files_change_the_colours_they_name() {
+        let theme = Theme::from_json(
+            r##"{"base": "dark", "food": "#102030", "border": 244, "snakes": ["#ffffff", 3]}"##,
+        )
+        .unwrap();
+        let dark = Theme::named("dark").unwrap();
+        assert_eq!(theme.food, Color::Rgb(16, 32, 48));
+        assert_eq!(theme.border, Color::Ansi(244));
+        assert_eq!(theme.fatal, dark.fatal);
+        assert_eq!(theme.snakes, [Color::Rgb(255, 255, 255), Color::Ansi(3)]);
+        assert_eq!(theme.snake(3), Color::Ansi(3));
+
+        let classic = Theme::from_json("{}").unwrap();
+        assert_eq!(classic.food, Theme::classic().food);
+    }
+
+    #[test]
+    fn invalid_theme_files_are_rejected() {
+        for text in [
+            "[]",
+            "not json",
+            r#"{"base": "neon"}"#,
+            r##"{"food": "#12345"}"##,
+            r##"{"food": "#gg0000"}"##,
+            r#"{"border": 256}"#,
+            r#"{"snakes": []}"#,
+            r##"{"snakes": "#ffffff"}"##,
+        ] {
+            assert!(Theme::from_json(text).is_err(), "{text}");
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How often loops check for input and resizes between ticks
+pub const INPUT_POLL: Duration = Duration::from_millis(10);
+
+// Fixed-timestep clock for the simulation. Every tick is scheduled one interval after the
+// previous one was due, so the time spent on input and rendering does not add up. A tick that
+// falls more than a whole interval behind is dropped instead of run in a burst.
+pub struct Ticker {
+    interval: Duration,
+    next: Instant,
+    dropped: u64,
+}
+
+impl Ticker {
+    pub fn new(interval: Duration) -> Self {
+        Self::starting_at(interval, Instant::now())
+    }
+
+    fn starting_at(interval: Duration, start: Instant) -> Self {
+        Self {
+            interval,
+            next: start + interval,
+            dropped: 0,
+        }
+    }
+
+    // Schedule the next tick one interval from now, after the clock stood still during a pause
+    pub fn restart(&mut self) {
+        self.next = Instant::now() + self.interval;
+    }
+
+    // Change the interval, starting with the tick after the one already scheduled
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    // Whether a tick is due, in which case the one after it is scheduled
+    pub fn tick(&mut self) -> bool {
+        self.tick_at(Instant::now())
+    }
+
+    fn tick_at(&mut self, now: Instant) -> bool {
+        if now < self.next {
+            return false;
+        }
+        self.next += self.interval;
+        if now >= self.next {
+            let missed =
+                ((now - self.next).as_nanos() / self.interval.as_nanos().max(1)) as u32 + 1;
+            self.dropped += u64::from(missed);
+            self.next += self.interval * missed;
+        }
+        true
+    }
+
+    // Wait until the next tick is due or `poll` has passed, whichever comes first
+    pub fn sleep(&self, poll: Duration) {
+        thread::sleep(
+            self.next
+                .saturating_duration_since(Instant::now())
+                .min(poll),
+        );
+    }
+
+    // Number of ticks dropped because the game fell behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn ticks_once_per_interval() {
+        let start = Instant::now();
+        let mut ticker = Ticker::starting_at(INTERVAL, start);
+        assert!(!ticker.tick_at(start));
+        assert!(!ticker.tick_at(start + INTERVAL / 2));
+        assert!(ticker.tick_at(start + INTERVAL));
+        assert!(!ticker.tick_at(start + INTERVAL * 3 / 2));
+        assert!(ticker.tick_at(start + INTERVAL * 2));
+        assert_eq!(ticker.dropped(), 0);
+    }
+
+    #[test]
+    fn late_ticks_keep_the_schedule() {
+        let start = Instant::now();
+        let mut ticker = Ticker::starting_at(INTERVAL, start);
+        // Less than an interval late, so the next tick is still due at its usual time
+        assert!(ticker.tick_at(start + INTERVAL * 19 / 10));
+        assert!(ticker.tick_at(start + INTERVAL * 2));
+        assert!(!ticker.tick_at(start + INTERVAL * 29 / 10));
+        assert!(ticker.tick_at(start + INTERVAL * 3));
+        assert_eq!(ticker.dropped(), 0);
+    }
+
+    #[test]
+    fn drops_ticks_more_than_an_interval_behind() {
+        let start = Instant::now();
+        let mut ticker = Ticker::starting_at(INTERVAL, start);
+        // The ticks due at 2 and 3 intervals are dropped rather than run in a burst
+        assert!(ticker.tick_at(start + INTERVAL * 35 / 10));
+        assert_eq!(ticker.dropped(), 2);
+        assert!(!ticker.tick_at(start + INTERVAL * 39 / 10));
+        assert!(ticker.tick_at(start + INTERVAL * 4));
+        assert_eq!(ticker.dropped(), 2);
+    }
+
+    #[test]
+    fn interval_changes_after_the_scheduled_tick() {
+        let start = Instant::now();
+        let mut ticker = Ticker::starting_at(INTERVAL, start);
+        ticker.set_interval(INTERVAL * 2);
+        assert!(ticker.tick_at(start + INTERVAL));
+        assert!(!ticker.tick_at(start + INTERVAL * 2));
+        assert!(ticker.tick_at(start + INTERVAL * 3));
+    }
+}